@@ -0,0 +1,895 @@
+//! Declarative code generator.
+//!
+//! Builds a self-contained `macro_rules!` (a tt-muncher) that resolves the
+//! arguments without calling a proc macro at the call site. The muncher is a
+//! local helper macro that every call defines, so the docs of the macro only
+//! show its entry point.
+//!
+//! The muncher state looks like this:
+//!
+//! `@fancyargs <position> [<order>] [<receiver>] [<generic 0>] .. [<arg 0>] .. [<arg n>] [<vararg>] ; <input>`
+//!
+//! * `<position>` is `p<i>` while positional arguments are consumed, and
+//!   `[kw <position>]` once a keyword argument was seen, which keeps the
+//!   number of positional arguments for error messages. It is `kw` once the
+//!   values are bound before the call.
+//! * `<order>` is `in_order` or `reordered`, followed by the keys of the
+//!   slots (`s<i>` and `va`) in the order they were passed. It is
+//!   `reordered` once an argument was passed after a later one, then the
+//!   values are bound in that order before the call (`@fancyargs_ordered`).
+//!   A partial call (`@partial`) starts as `reordered partial`, its values
+//!   are always bound, and the missing required arguments become the
+//!   parameters of the returned closure (`@fancyargs_partial`).
+//! * Every argument slot is an empty `[]` until it is filled with `[$value]`.
+//! * The vararg slot collects elements as `[($a) ($b)]` and spread iterators
+//!   (`..iter`) as `{$iter}`, or holds `($value)` if the vararg was passed as
+//!   a keyword argument.
+//! * The receiver slot only exists for methods. It holds a variable, or the
+//!   receiver bound by the entry arm.
+//! * Every generic slot is `[_]` until it is set with `T = type` (or `N = value`
+//!   for const generics), the function is called with a turbofish if any of
+//!   them was set.
+
+use proc_macro2::{Group, Span, TokenStream, TokenTree};
+use quote::quote;
+
+use crate::{error::Errors, expand, parse::ArgCaptured};
+
+/// A single argument slot of the muncher state.
+struct Slot<'a> {
+    def: &'a ArgCaptured,
+    /// The keyword, `None` for patterns that can only be passed positionally.
+    name: Option<syn::Ident>,
+    var: syn::Ident,
+}
+
+impl<'a> Slot<'a> {
+    /// Pattern that matches any slot value.
+    fn pat(&self) -> TokenStream {
+        let var = &self.var;
+        quote!( $#var:tt )
+    }
+
+    /// Transcription of a slot matched with `pat()`.
+    fn var(&self) -> TokenStream {
+        let var = &self.var;
+        quote!( $#var )
+    }
+}
+
+fn ident(name: &str) -> syn::Ident {
+    syn::Ident::new(name, Span::call_site())
+}
+
+/// The keyword of an argument, raw identifiers can not be created from their
+/// name.
+fn keyword(def: &ArgCaptured) -> Option<syn::Ident> {
+    def.name_ident().map(|name| {
+        let mut name = name.clone();
+        name.set_span(Span::call_site());
+        name
+    })
+}
+
+/// The keywords of an argument, its name and its aliases, with the start of
+/// the arms that match `keyword = $v` and the value that is stored in the
+/// slot for `$v`.
+///
+/// Aliases are matched by `@fancyargs_alias` arms, which get the keyword of
+/// the call as `$kw`, so the deprecation warning points at the call site.
+fn keywords(name: &syn::Ident, def: &ArgCaptured) -> Vec<(TokenStream, syn::Ident, TokenStream)> {
+    let mut keywords = vec![(quote!( @fancyargs ), name.clone(), quote!( $v ))];
+    for alias in &def.aliases {
+        let value = expand::deprecated_alias(def, alias, quote!( $v ), quote!( $kw ));
+        keywords.push((quote!( @fancyargs_alias [$kw:ident] ), alias.name.clone(), value));
+    }
+    keywords
+}
+
+/// Checks that all arguments are supported by the declarative generator.
+pub fn validate(args: &[ArgCaptured], errors: &mut Errors) {
+    for arg in args {
+        if arg.is_kwargs() {
+            errors.spanned(
+                &arg.pat,
+                "Invalid kwargs argument: kwargs are not supported by declarative macros",
+            );
+        }
+    }
+}
+
+/// Builds the declarative `macro_rules!` for a function.
+///
+/// The arguments must have been checked with `validate()`.
+///
+/// `path` is the path of the target function and `definition` its cleaned
+/// up definition. Methods are called by name on the first positional
+/// argument instead, and the generic parameters of the function itself can
+/// be set at the call site. If some defaults are hoisted (see
+/// `expand::hoisted()`), the wrapper of the function is called instead.
+/// `inherit_arm` passes the parameters on for `..inherit()` directives. It is
+/// the only arm besides the entry point, because it expands to an item.
+pub fn build_macro(
+    macro_ident: &syn::Ident,
+    path: &syn::Path,
+    definition: &syn::ItemFn,
+    args: &[ArgCaptured],
+    generics: &expand::Generics,
+    inherit_arm: TokenStream,
+) -> TokenStream {
+    let receiver = definition
+        .decl
+        .inputs
+        .iter()
+        .any(|arg| matches!(arg, syn::FnArg::SelfRef(_) | syn::FnArg::SelfValue(_)));
+    // The receiver is bound by reference for `&self` and `&mut self`.
+    let borrow = match definition.decl.inputs.first().map(|pair| pair.into_value()) {
+        Some(syn::FnArg::SelfRef(arg)) => {
+            let mutability = &arg.mutability;
+            quote!( & #mutability )
+        }
+        _ => quote!(),
+    };
+    let hoisted = &expand::hoisted(args, definition.constness.is_some());
+    // The arms call a local helper macro, which every call defines, since
+    // macro expanded `#[macro_export]` macros can not be called through
+    // `$crate` inside of the defining crate.
+    let helper = ident(&format!("__fancyargs_{}", macro_ident.to_string().trim_start_matches("r#")));
+    let callee = quote!( #helper );
+    let signature = format!(
+        "\n  signature: {}",
+        expand::signature(&path.segments.last().unwrap().value().ident.to_string(), receiver, args)
+    );
+
+    let (fixed, vararg) = match args.last() {
+        Some(last) if last.is_vararg() => (&args[..args.len() - 1], Some(last)),
+        _ => (args, None),
+    };
+
+    let slots = fixed
+        .iter()
+        .enumerate()
+        .map(|(index, def)| Slot {
+            def,
+            name: keyword(def),
+            var: ident(&format!("s{}", index)),
+        })
+        .collect::<Vec<_>>();
+    let slot_pats = &slots.iter().map(Slot::pat).collect::<Vec<_>>();
+    let slot_vars = &slots.iter().map(Slot::var).collect::<Vec<_>>();
+
+    let (recv_pat, recv_var) = if receiver {
+        (quote!( $recv:tt ), quote!( $recv ))
+    } else {
+        (quote!(), quote!())
+    };
+    let (va_pat, va_var) = if vararg.is_some() {
+        (quote!( $va:tt ), quote!( $va ))
+    } else {
+        (quote!(), quote!())
+    };
+    // Type and const parameters of the function, lifetimes can not be set.
+    let generic_params = definition
+        .decl
+        .generics
+        .params
+        .iter()
+        .filter_map(|param| match param {
+            syn::GenericParam::Type(param) => Some((&param.ident, true)),
+            syn::GenericParam::Const(param) => Some((&param.ident, false)),
+            syn::GenericParam::Lifetime(_) => None,
+        })
+        .collect::<Vec<_>>();
+    let gen_pats = &(0..generic_params.len())
+        .map(|index| {
+            let var = ident(&format!("g{}", index));
+            quote!( $#var:tt )
+        })
+        .collect::<Vec<_>>();
+    let gen_vars = &(0..generic_params.len())
+        .map(|index| {
+            let var = ident(&format!("g{}", index));
+            quote!( $#var )
+        })
+        .collect::<Vec<_>>();
+    let empty_gens = &generic_params.iter().map(|_| quote!( [_] )).collect::<Vec<_>>();
+    // Everything in front of the argument slots.
+    let head_pat = quote!( $ord:tt #recv_pat #( #gen_pats )* );
+    let head_var = quote!( $ord #recv_var #( #gen_vars )* );
+    // Like `head_pat`, for arms that record the key of the slot they fill.
+    let push_pat = quote!( [$order:tt $($keys:tt)*] #recv_pat #( #gen_pats )* );
+    let push_var = |order: TokenStream, key: &syn::Ident| {
+        quote!( [#order $($keys)* #key] #recv_var #( #gen_vars )* )
+    };
+    let va_key = ident("va");
+
+    let mut arms = Vec::new();
+
+    // Finish once all input is consumed.
+    let path = &if hoisted.contains(&true) {
+        expand::wrapper_path(path)
+    } else {
+        path.clone()
+    };
+    let call = |args: TokenStream, turbofish: &TokenStream| {
+        if receiver {
+            let method = &path.segments.last().unwrap().value().ident;
+            quote!( ($recv).#method #turbofish (#args) )
+        } else {
+            quote!( #path #turbofish (#args) )
+        }
+    };
+    // If a default at the call site refers to earlier arguments, the
+    // explicit arguments are bound first and the defaults are computed from
+    // these bindings.
+    let names = expand::param_names(args);
+    let deps = fixed
+        .iter()
+        .zip(hoisted)
+        .map(|(def, hoisted)| {
+            if *hoisted {
+                return Vec::new();
+            }
+            let mut deps = expand::default_refs(def, &names)
+                .into_iter()
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>();
+            deps.sort();
+            deps.dedup();
+            deps
+        })
+        .collect::<Vec<_>>();
+    let dependent = deps.iter().any(|deps| !deps.is_empty());
+    let bindings = &(0..args.len())
+        .map(|index| expand::binding(&names, index))
+        .collect::<Vec<_>>();
+    let finish = |va_value: TokenStream, turbofish: &TokenStream| {
+        if !dependent {
+            let values = slots.iter().map(|slot| {
+                let key = &slot.var;
+                let var = slot.var();
+                quote!( #callee!(@fancyargs_value #key #var) )
+            });
+            return call(quote!( #( #values, )* #va_value ), turbofish);
+        }
+        let supplied = slots.iter().map(|slot| {
+            let key = &slot.var;
+            let var = slot.var();
+            quote!( #callee!(@fancyargs_supplied #key #var) )
+        });
+        let values = slots.iter().zip(bindings).zip(&deps).map(|((slot, binding), deps)| {
+            let key = &slot.var;
+            let var = slot.var();
+            let deps = deps.iter().map(|index| &bindings[*index]);
+            quote!( let #binding = #callee!(@fancyargs_value #key #var #binding [ #( #deps )* ]); )
+        });
+        let call = call(quote!( #( #bindings ),* ), turbofish);
+        quote!(
+            match ( #( #supplied, )* #va_value ) {
+                ( #( #bindings, )* ) => {
+                    #( #values )*
+                    #call
+                }
+            }
+        )
+    };
+    let recv_final = if receiver {
+        quote!( [$recv:expr] )
+    } else {
+        quote!()
+    };
+    // Without explicit generic arguments the function is called without a
+    // turbofish, otherwise the unset ones are inferred with `_`.
+    let mut finals = vec![(
+        quote!( [in_order $($keys:tt)*] #recv_final #( #empty_gens )* ),
+        quote!(),
+    )];
+    if !generic_params.is_empty() {
+        finals.push((
+            quote!( [in_order $($keys:tt)*] #recv_final #( [#gen_pats] )* ),
+            quote!( ::< #( #gen_vars ),* > ),
+        ));
+    }
+    for (head_final, turbofish) in &finals {
+        match vararg {
+            Some(def) => {
+                let element = expand::vararg_element(def, generics, quote!($va), Span::call_site());
+                let collected = finish(expand::vararg(def, quote!( $( #element ),* )), turbofish);
+                let chained = match expand::vararg_chain(def, vec![quote!( #callee!(@fancyargs_chain $($va)*) )]) {
+                    Ok(value) => finish(value, turbofish),
+                    Err(message) => quote!( ::core::compile_error!(#message) ),
+                };
+                let direct = finish(
+                    expand::supplied(def, generics, quote!($va), Span::call_site()),
+                    turbofish,
+                );
+                arms.push(quote!(
+                    (@fancyargs $pos:tt #head_final #( #slot_pats )* [ $( ($va:expr) )* ] ;) => {
+                        #collected
+                    };
+                    (@fancyargs $pos:tt #head_final #( #slot_pats )* [ $($va:tt)* ] ;) => {
+                        #chained
+                    };
+                    (@fancyargs $pos:tt #head_final #( #slot_pats )* ( $va:expr ) ;) => {
+                        #direct
+                    };
+                ));
+            }
+            None => {
+                let call = finish(quote!(), turbofish);
+                arms.push(quote!(
+                    (@fancyargs $pos:tt #head_final #( #slot_pats )* ;) => {
+                        #call
+                    };
+                ));
+            }
+        }
+    }
+
+    // If the arguments were reordered, the values are bound in the order
+    // they were passed, before the call. The receiver is already bound by the entry arm, unless
+    // it is a variable.
+    let state_pat = quote!( #recv_pat #( #gen_pats )* #( #slot_pats )* #va_pat );
+    let state_var = quote!( #recv_var #( #gen_vars )* #( #slot_vars )* #va_var );
+    arms.push(quote!(
+        (@fancyargs $pos:tt [reordered partial $($keys:tt)*] #state_pat ;) => {
+            #callee!(@fancyargs_ordered [$($keys)* partial] #state_var)
+        };
+        (@fancyargs $pos:tt [reordered $($keys:tt)*] #state_pat ;) => {
+            #callee!(@fancyargs_ordered [$($keys)*] #state_var)
+        };
+        (@fancyargs_ordered [] $($state:tt)*) => {
+            #callee!(@fancyargs kw [in_order] $($state)* ;)
+        };
+    ));
+
+    // Once the values of a partial call are bound, every missing required
+    // argument is replaced with a parameter of the closure.
+    let required = slots
+        .iter()
+        .enumerate()
+        .filter(|(index, slot)| expand::omitted(slot.def, hoisted[*index]).is_none())
+        .collect::<Vec<_>>();
+    let end = ident("end");
+    let first = required.first().map(|(_, slot)| &slot.var).unwrap_or(&end);
+    arms.push(quote!(
+        (@fancyargs_ordered [partial] $($state:tt)*) => {
+            #callee!(@fancyargs_partial #first [] $($state)*)
+        };
+        (@fancyargs_partial end [$($params:tt)*] $($state:tt)*) => {
+            move | $($params)* | #callee!(@fancyargs kw [in_order] $($state)* ;)
+        };
+    ));
+    for (position, (index, slot)) in required.iter().enumerate() {
+        let key = &slot.var;
+        let next = required.get(position + 1).map(|(_, slot)| &slot.var).unwrap_or(&end);
+        let param = ident(&format!("__fancyargs_param{}", index));
+        let param_decl = match generics.closure_param_ty(&slot.def.ty) {
+            Some(ty) => quote!( #param: #ty, ),
+            None => quote!( #param, ),
+        };
+        let empty_pats = slots.iter().enumerate().map(|(other, slot)| {
+            if other == *index {
+                quote!( [] )
+            } else {
+                slot.pat()
+            }
+        });
+        let param_vars = slots.iter().enumerate().map(|(other, slot)| {
+            if other == *index {
+                quote!( [#param] )
+            } else {
+                slot.var()
+            }
+        });
+        arms.push(quote!(
+            (@fancyargs_partial #key [$($params:tt)*] #recv_pat #( #gen_pats )* #( #empty_pats )* #va_pat) => {
+                #callee!(@fancyargs_partial #next [$($params)* #param_decl] #recv_var #( #gen_vars )* #( #param_vars )* #va_var)
+            };
+            (@fancyargs_partial #key $params:tt $($state:tt)*) => {
+                #callee!(@fancyargs_partial #next $params $($state)*)
+            };
+        ));
+    }
+    let bound = ident("__fancyargs_bound");
+    for (index, slot) in slots.iter().enumerate() {
+        let key = &slot.var;
+        let value_pats = slots.iter().enumerate().map(|(other, slot)| {
+            if other == index {
+                quote!( [$v:expr] )
+            } else {
+                slot.pat()
+            }
+        });
+        let bound_vars = slots.iter().enumerate().map(|(other, slot)| {
+            if other == index {
+                quote!( [#bound] )
+            } else {
+                slot.var()
+            }
+        });
+        // `&mut` references are reborrowed, like in a plain call.
+        let value = if expand::reborrowed(slot.def) {
+            quote!( &mut *($v) )
+        } else {
+            quote!( $v )
+        };
+        arms.push(quote!(
+            (@fancyargs_ordered [#key $($keys:tt)*] #recv_pat #( #gen_pats )* #( #value_pats )* #va_pat) => {
+                match #value {
+                    #bound => #callee!(@fancyargs_ordered [$($keys)*] #recv_var #( #gen_vars )* #( #bound_vars )* #va_var),
+                }
+            };
+        ));
+    }
+    if let Some(def) = vararg {
+        let element = expand::vararg_element(def, generics, quote!($va), Span::call_site());
+        let collected = expand::vararg(def, quote!( $( #element ),* ));
+        let chained = expand::vararg_chain(def, vec![quote!( #callee!(@fancyargs_chain $($va)*) )])
+            .unwrap_or_else(|message| quote!( ::core::compile_error!(#message) ));
+        let head = quote!( [#va_key $($keys:tt)*] #recv_pat #( #gen_pats )* #( #slot_pats )* );
+        let rest = quote!( #callee!(@fancyargs_ordered [$($keys)*] #recv_var #( #gen_vars )* #( #slot_vars )* (#bound)) );
+        arms.push(quote!(
+            (@fancyargs_ordered #head [ $( ($va:expr) )* ]) => {
+                match #collected {
+                    #bound => #rest,
+                }
+            };
+            (@fancyargs_ordered #head [ $($va:tt)* ]) => {
+                match #chained {
+                    #bound => #rest,
+                }
+            };
+            (@fancyargs_ordered #head ( $va:expr )) => {
+                match $va {
+                    #bound => #rest,
+                }
+            };
+        ));
+    }
+
+    // Explicit generic arguments, which don't end the positional arguments.
+    for (index, (name, is_type)) in generic_params.iter().enumerate() {
+        let fill_pats = gen_pats.iter().enumerate().map(|(other, pat)| {
+            if other == index {
+                quote!( [_] )
+            } else {
+                pat.clone()
+            }
+        });
+        let value = if *is_type {
+            quote!( $v )
+        } else {
+            quote!( { $v } )
+        };
+        let fill_vars = gen_vars.iter().enumerate().map(|(other, var)| {
+            if other == index {
+                quote!( [#value] )
+            } else {
+                var.clone()
+            }
+        });
+        let frag = if *is_type {
+            quote!( ty )
+        } else {
+            quote!( expr )
+        };
+        let duplicate = format!("Duplicate generic argument '{}'", name);
+        arms.push(quote!(
+            (@fancyargs $pos:tt $ord:tt #recv_pat #( #fill_pats )* #( #slot_pats )* #va_pat ; #name = $v:#frag $(, $($rest:tt)*)?) => {
+                #callee!(@fancyargs $pos $ord #recv_var #( #fill_vars )* #( #slot_vars )* #va_var ; $($($rest)*)?)
+            };
+            (@fancyargs $pos:tt #head_pat #( #slot_pats )* #va_pat ; #name = $($rest:tt)*) => {
+                ::core::compile_error!(#duplicate)
+            };
+        ));
+    }
+
+    // Positional arguments, up to the first keyword-only argument.
+    let positional_len = slots
+        .iter()
+        .take_while(|slot| !slot.def.is_kw_only())
+        .count();
+
+    // Keyword arguments.
+    for (index, slot) in slots.iter().enumerate() {
+        let name = match slot.name {
+            Some(ref name) => name,
+            None => continue,
+        };
+        if slot.def.is_pos_only() {
+            let pos_only = format!(
+                "Invalid keyword argument: '{}' is a positional-only argument",
+                name
+            );
+            arms.push(quote!(
+                (@fancyargs $pos:tt #head_pat #( #slot_pats )* #va_pat ; #name = $($rest:tt)*) => {
+                    ::core::compile_error!(#pos_only)
+                };
+            ));
+            continue;
+        }
+        let fill_pats = &slots
+            .iter()
+            .enumerate()
+            .map(|(other, slot)| {
+                if other == index {
+                    quote!( [] )
+                } else {
+                    slot.pat()
+                }
+            })
+            .collect::<Vec<_>>();
+        for (tag, keyword, value) in keywords(name, slot.def) {
+            // Flags are rewritten to keyword arguments, before a bare
+            // keyword is taken as positional argument.
+            if slot.def.is_flag() {
+                let empty_vars = slots.iter().enumerate().map(|(other, slot)| {
+                    if other == index {
+                        quote!( [] )
+                    } else {
+                        slot.var()
+                    }
+                });
+                let empty_vars = &empty_vars.collect::<Vec<_>>();
+                let duplicate = expand::duplicate_flag(&keyword.to_string());
+                // Where a positional argument can be passed, the keyword
+                // could also be a variable.
+                let ambiguous = expand::ambiguous_flag(&keyword.to_string(), true);
+                let ambiguous_not = expand::ambiguous_flag(&keyword.to_string(), false);
+                for position in 0..positional_len {
+                    let pos = ident(&format!("p{}", position));
+                    arms.push(quote!(
+                        (@fancyargs #pos #head_pat #( #slot_pats )* #va_pat ; #keyword $(, $($rest:tt)*)?) => {
+                            ::core::compile_error!(#ambiguous)
+                        };
+                        (@fancyargs #pos #head_pat #( #slot_pats )* #va_pat ; ! #keyword $(, $($rest:tt)*)?) => {
+                            ::core::compile_error!(#ambiguous_not)
+                        };
+                    ));
+                }
+                if vararg.is_some() {
+                    arms.push(quote!(
+                        (@fancyargs $pos:tt #head_pat #( #slot_pats )* [ $($va:tt)* ] ; #keyword $(, $($rest:tt)*)?) => {
+                            ::core::compile_error!(#ambiguous)
+                        };
+                        (@fancyargs $pos:tt #head_pat #( #slot_pats )* [ $($va:tt)* ] ; ! #keyword $(, $($rest:tt)*)?) => {
+                            ::core::compile_error!(#ambiguous_not)
+                        };
+                    ));
+                }
+                arms.push(quote!(
+                    (@fancyargs $pos:tt #head_pat #( #fill_pats )* #va_pat ; #keyword $(, $($rest:tt)*)?) => {
+                        #callee!(@fancyargs $pos #head_var #( #empty_vars )* #va_var ; #keyword = true $(, $($rest)*)?)
+                    };
+                    (@fancyargs $pos:tt #head_pat #( #fill_pats )* #va_pat ; ! #keyword $(, $($rest:tt)*)?) => {
+                        #callee!(@fancyargs $pos #head_var #( #empty_vars )* #va_var ; #keyword = false $(, $($rest)*)?)
+                    };
+                    (@fancyargs $pos:tt #head_pat #( #slot_pats )* #va_pat ; $(!)? #keyword $(, $($rest:tt)*)?) => {
+                        ::core::compile_error!(#duplicate)
+                    };
+                ));
+            }
+            let fill_vars = slots.iter().enumerate().map(|(other, slot)| {
+                if other == index {
+                    quote!( [#value] )
+                } else {
+                    slot.var()
+                }
+            });
+            // The order is kept if no later argument was passed yet.
+            let later_pats = slots.iter().enumerate().map(|(other, slot)| {
+                if other >= index {
+                    quote!( [] )
+                } else {
+                    slot.pat()
+                }
+            });
+            let later_vars = slots.iter().enumerate().map(|(other, slot)| {
+                if other == index {
+                    quote!( [#value] )
+                } else if other > index {
+                    quote!( [] )
+                } else {
+                    slot.var()
+                }
+            });
+            let later_va = if vararg.is_some() {
+                quote!( [] )
+            } else {
+                quote!()
+            };
+            let in_order = push_var(quote!( $order ), &slot.var);
+            let reordered = push_var(quote!( reordered ), &slot.var);
+            let duplicate = format!("Duplicate keyword argument '{}'", keyword);
+            arms.push(quote!(
+                (#tag $pos:tt #push_pat #( #later_pats )* #later_va ; #keyword = $v:expr $(, $($rest:tt)*)?) => {
+                    #callee!(@fancyargs [kw $pos] #in_order #( #later_vars )* #later_va ; $($($rest)*)?)
+                };
+                (#tag $pos:tt #push_pat #( #fill_pats )* #va_pat ; #keyword = $v:expr $(, $($rest:tt)*)?) => {
+                    #callee!(@fancyargs [kw $pos] #reordered #( #fill_vars )* #va_var ; $($($rest)*)?)
+                };
+                (#tag $pos:tt #head_pat #( #slot_pats )* #va_pat ; #keyword = $($rest:tt)*) => {
+                    ::core::compile_error!(#duplicate)
+                };
+            ));
+        }
+    }
+    if let Some((def, name)) = vararg.and_then(|def| keyword(def).map(|name| (def, name))) {
+        for (tag, keyword, value) in keywords(&name, def) {
+            let duplicate = format!("Duplicate keyword argument '{}'", keyword);
+            let push = push_var(quote!( $order ), &va_key);
+            arms.push(quote!(
+                (#tag $pos:tt #push_pat #( #slot_pats )* [] ; #keyword = $v:expr $(, $($rest:tt)*)?) => {
+                    #callee!(@fancyargs [kw $pos] #push #( #slot_vars )* (#value) ; $($($rest)*)?)
+                };
+                (#tag $pos:tt #head_pat #( #slot_pats )* #va_pat ; #keyword = $($rest:tt)*) => {
+                    ::core::compile_error!(#duplicate)
+                };
+            ));
+        }
+    }
+
+    // Other keywords are checked against the aliases, and are unknown
+    // otherwise. The position is unwrapped to tell which arguments were
+    // passed positionally, and likely typos get a suggestion.
+    arms.push(quote!(
+        (@fancyargs $pos:tt #head_pat #( #slot_pats )* #va_pat ; $name:ident = $($rest:tt)*) => {
+            #callee!(@fancyargs_alias [$name] $pos #head_var #( #slot_vars )* #va_var ; $name = $($rest)*)
+        };
+        (@fancyargs_alias [$name:ident] $pos:tt $($rest:tt)*) => {
+            #callee!(@fancyargs_unknown $pos $name)
+        };
+        (@fancyargs_unknown [kw $pos:tt] $name:ident) => {
+            #callee!(@fancyargs_unknown $pos $name)
+        };
+    ));
+    for passed in 0..=positional_len {
+        let pos = ident(&format!("p{}", passed));
+        let context = signature.clone() + &expand::passed_positionally(args, receiver, &(0..passed).collect::<Vec<_>>());
+        arms.push(quote!(
+            (@fancyargs_unknown #pos $name:ident) => {
+                #callee!(@fancyargs_unknown_keyword $name #context)
+            };
+        ));
+    }
+    let mut typos = Vec::new();
+    for keyword in args.iter().filter(|arg| !arg.is_pos_only()).filter_map(ArgCaptured::name) {
+        for typo in expand::typos(&keyword) {
+            let taken = args.iter().any(|arg| {
+                arg.name().filter(|name| *name == typo).is_some() || arg.aliases.iter().any(|alias| alias.name == typo)
+            });
+            if !taken && !typos.iter().any(|(other, _)| *other == typo) {
+                typos.push((typo, keyword.clone()));
+            }
+        }
+    }
+    for (typo, keyword) in typos {
+        let unknown = format!("Unknown keyword argument '{}', did you mean '{}'?", typo, keyword);
+        let typo = ident(&typo);
+        arms.push(quote!(
+            (@fancyargs_unknown_keyword #typo $context:expr) => {
+                ::core::compile_error!(::core::concat!(#unknown, $context))
+            };
+        ));
+    }
+    arms.push(quote!(
+        (@fancyargs_unknown_keyword $name:ident $context:expr) => {
+            ::core::compile_error!(::core::concat!("Unknown keyword argument '", ::core::stringify!($name), "'", $context))
+        };
+    ));
+    for index in 0..positional_len {
+        let pos = ident(&format!("p{}", index));
+        let next = ident(&format!("p{}", index + 1));
+        let fill_vars = slots.iter().enumerate().map(|(other, slot)| {
+            if other == index {
+                quote!( [$v] )
+            } else {
+                slot.var()
+            }
+        });
+        let push = push_var(quote!( $order ), &slots[index].var);
+        arms.push(quote!(
+            (@fancyargs #pos #push_pat #( #slot_pats )* #va_pat ; $v:expr $(, $($rest:tt)*)?) => {
+                #callee!(@fancyargs #next #push #( #fill_vars )* #va_var ; $($($rest)*)?)
+            };
+        ));
+    }
+    match vararg {
+        Some(def) => {
+            let given = format!(
+                "Invalid positional argument: vararg '{}' was already passed as keyword argument",
+                def.name().unwrap_or_default()
+            );
+            let push = push_var(quote!( $order ), &va_key);
+            arms.push(quote!(
+                (@fancyargs $pos:tt #push_pat #( #slot_pats )* [] ; .. $v:expr $(, $($rest:tt)*)?) => {
+                    #callee!(@fancyargs $pos #push #( #slot_vars )* [ {$v} ] ; $($($rest)*)?)
+                };
+                (@fancyargs $pos:tt #push_pat #( #slot_pats )* [] ; $v:expr $(, $($rest:tt)*)?) => {
+                    #callee!(@fancyargs $pos #push #( #slot_vars )* [ ($v) ] ; $($($rest)*)?)
+                };
+                (@fancyargs $pos:tt #head_pat #( #slot_pats )* [ $($va:tt)* ] ; .. $v:expr $(, $($rest:tt)*)?) => {
+                    #callee!(@fancyargs $pos #head_var #( #slot_vars )* [ $($va)* {$v} ] ; $($($rest)*)?)
+                };
+                (@fancyargs $pos:tt #head_pat #( #slot_pats )* [ $($va:tt)* ] ; $v:expr $(, $($rest:tt)*)?) => {
+                    #callee!(@fancyargs $pos #head_var #( #slot_vars )* [ $($va)* ($v) ] ; $($($rest)*)?)
+                };
+                (@fancyargs $pos:tt #head_pat #( #slot_pats )* ( $($va:tt)* ) ; $($rest:tt)+) => {
+                    ::core::compile_error!(#given)
+                };
+            ));
+        }
+        None => {
+            let too_many = match slots.get(positional_len) {
+                Some(slot) => format!(
+                    "Invalid positional argument: '{}' is a keyword-only argument",
+                    slot.def.name().unwrap_or_default()
+                ),
+                None => format!(
+                    "Invalid positional argument: function only takes {} arguments{}",
+                    slots.len(),
+                    signature
+                ),
+            };
+            let last = ident(&format!("p{}", positional_len));
+            arms.push(quote!(
+                (@fancyargs [kw $pos:tt] #head_pat #( #slot_pats )* ; $($rest:tt)+) => {
+                    ::core::compile_error!("Invalid positional argument: positional arguments may not follow after keyword arguments")
+                };
+                (@fancyargs #last #head_pat #( #slot_pats )* ; $($rest:tt)+) => {
+                    ::core::compile_error!(#too_many)
+                };
+            ));
+        }
+    }
+    arms.push(quote!(
+        (@fancyargs $($state:tt)*) => {
+            ::core::compile_error!("Invalid arguments")
+        };
+    ));
+
+    // Values of the individual arguments.
+    for (index, slot) in slots.iter().enumerate() {
+        let key = &slot.var;
+        let mut supplied = expand::supplied(slot.def, generics, quote!($v), Span::call_site());
+        if hoisted[index] {
+            supplied = expand::hoisted_supplied(supplied);
+        }
+        let omitted = expand::omitted(slot.def, hoisted[index]).unwrap_or_else(|| {
+            let missing = expand::missing(slot.def, index + receiver as usize) + &signature;
+            quote!( ::core::compile_error!(#missing) )
+        });
+        if !dependent {
+            arms.push(quote!(
+                (@fancyargs_value #key [$v:expr]) => { #supplied };
+                (@fancyargs_value #key []) => { #omitted };
+            ));
+            continue;
+        }
+        // The default is computed from the bindings of the earlier
+        // arguments, which are passed in as `[$d0 $d1 ..]`, since the
+        // identifiers of this arm can not refer to them.
+        let dep_var = |index: usize| {
+            let var = ident(&format!("d{}", index));
+            quote!( $#var )
+        };
+        let dep_pats = deps[index].iter().map(|index| {
+            let var = dep_var(*index);
+            quote!( #var:ident )
+        });
+        let omitted = expand::replace_refs(omitted, &names, dep_var);
+        arms.push(quote!(
+            (@fancyargs_supplied #key [$v:expr]) => { #supplied };
+            (@fancyargs_supplied #key []) => { () };
+            (@fancyargs_value #key [$v:expr] $b:ident [ $($d:tt)* ]) => { $b };
+            (@fancyargs_value #key [] $b:ident [ #( #dep_pats )* ]) => {{
+                let () = $b;
+                #omitted
+            }};
+        ));
+    }
+
+    // Iterator of a vararg with spread elements, the parts are chained in
+    // order.
+    if let Some(def) = vararg {
+        let element = expand::vararg_element(def, generics, quote!($v), Span::call_site());
+        let elements = expand::vararg_elements_iter(element);
+        let spread = expand::vararg_spread_iter(def, generics, quote!($v));
+        arms.push(quote!(
+            (@fancyargs_part ($v:expr)) => { #elements };
+            (@fancyargs_part {$v:expr}) => { #spread };
+            (@fancyargs_chain $part:tt) => { #callee!(@fancyargs_part $part) };
+            (@fancyargs_chain $part:tt $($rest:tt)+) => {
+                ::core::iter::Iterator::chain(
+                    #callee!(@fancyargs_part $part),
+                    #callee!(@fancyargs_chain $($rest)+)
+                )
+            };
+        ));
+    }
+
+    // Entry point.
+    let empty_slots = slots.iter().map(|_| quote!( [] ));
+    let empty_va = if vararg.is_some() {
+        quote!( [] )
+    } else {
+        quote!()
+    };
+    if receiver {
+        let empty_slots = &empty_slots.collect::<Vec<_>>();
+        // The receiver is evaluated before the arguments. A variable has
+        // nothing to evaluate and stays in place, except in a partial call,
+        // which binds all values right away.
+        arms.push(quote!(
+            (@partial $recv:expr $(, $($input:tt)*)?) => {
+                match #borrow ($recv) {
+                    __fancyargs_recv => #callee!(@fancyargs p0 [reordered partial] [__fancyargs_recv] #( #empty_gens )* #( #empty_slots )* #empty_va ; $($($input)*)?),
+                }
+            };
+            () => {
+                ::core::compile_error!("Missing receiver: the first argument of a method must be positional")
+            };
+            ($name:ident = $($input:tt)*) => {
+                ::core::compile_error!("Missing receiver: the first argument of a method must be positional")
+            };
+            ($recv:ident $(, $($input:tt)*)?) => {
+                #callee!(@fancyargs p0 [in_order] [$recv] #( #empty_gens )* #( #empty_slots )* #empty_va ; $($($input)*)?)
+            };
+            ($recv:expr $(, $($input:tt)*)?) => {
+                match #borrow ($recv) {
+                    __fancyargs_recv => #callee!(@fancyargs p0 [in_order] [__fancyargs_recv] #( #empty_gens )* #( #empty_slots )* #empty_va ; $($($input)*)?),
+                }
+            };
+        ));
+    } else {
+        let empty_slots = &empty_slots.collect::<Vec<_>>();
+        arms.push(quote!(
+            (@partial $($input:tt)*) => {
+                #callee!(@fancyargs p0 [reordered partial] #( #empty_gens )* #( #empty_slots )* #empty_va ; $($input)*)
+            };
+            ( $($input:tt)* ) => {
+                #callee!(@fancyargs p0 [in_order] #( #empty_gens )* #( #empty_slots )* #empty_va ; $($input)*)
+            };
+        ));
+    }
+
+    // Only the entry points show up in the docs of the macro. The `$` of the
+    // helper are passed in as `$d`, because a nested `macro_rules!` can not
+    // write them directly.
+    let arms = escape_dollars(quote!( #( #arms )* ));
+    quote!(
+        macro_rules! #macro_ident {
+            #inherit_arm
+            ( $($input:tt)* ) => {{
+                macro_rules! __fancyargs_dollar {
+                    ($args:tt $d:tt) => {{
+                        macro_rules! #helper {
+                            #arms
+                        }
+                        #helper! $args
+                    }};
+                }
+                __fancyargs_dollar!(($($input)*) $)
+            }};
+        }
+    )
+}
+
+/// Replaces every `$` with `$d`.
+fn escape_dollars(tokens: TokenStream) -> TokenStream {
+    tokens
+        .into_iter()
+        .flat_map(|token| match token {
+            TokenTree::Punct(ref punct) if punct.as_char() == '$' => {
+                vec![token.clone(), TokenTree::Ident(ident("d"))]
+            }
+            TokenTree::Group(group) => {
+                let mut escaped = Group::new(group.delimiter(), escape_dollars(group.stream()));
+                escaped.set_span(group.span());
+                vec![TokenTree::Group(escaped)]
+            }
+            token => vec![token],
+        })
+        .collect()
+}
@@ -0,0 +1,1037 @@
+//! Expansion of individual arguments.
+//!
+//! Shared by `invoke!()` and the declarative macros, so both code generators
+//! produce the same expressions for a given argument.
+
+use proc_macro2::{Spacing, Span, TokenStream, TokenTree};
+use quote::{quote, quote_spanned, ToTokens};
+use syn::spanned::Spanned;
+
+use crate::parse::{Alias, ArgCaptured};
+
+/// Generic parameters of the target function and its impl block.
+///
+/// Types that refer to them can not be named at the call site.
+#[derive(Default)]
+pub struct Generics {
+    names: Vec<String>,
+}
+
+impl Generics {
+    pub fn new<'a, I: IntoIterator<Item = &'a syn::Generics>>(generics: I) -> Self {
+        let names = generics
+            .into_iter()
+            .flat_map(|generics| generics.params.iter())
+            .filter_map(|param| match param {
+                syn::GenericParam::Type(ty) => Some(ty.ident.to_string()),
+                syn::GenericParam::Const(param) => Some(param.ident.to_string()),
+                syn::GenericParam::Lifetime(_) => None,
+            })
+            .collect();
+        Generics { names }
+    }
+
+    /// The type as it can be written at the call site.
+    ///
+    /// Named lifetimes are replaced with `'_`, so they are inferred.
+    /// Returns `None` unless the type is built from primitive types like
+    /// `&str` or `(u8, [bool; 2])`, since all other names may not be in
+    /// scope at the call site.
+    pub fn call_site_ty<T: ToTokens>(&self, ty: &T) -> Option<TokenStream> {
+        self.call_site_tokens(ty.into_token_stream())
+    }
+
+    /// The type of a closure parameter for the argument, for partial calls.
+    ///
+    /// References and the other types that are built into the language are
+    /// kept, with their lifetimes elided, so that the closure accepts a
+    /// borrow with a new lifetime on every call. Names other than primitive
+    /// types are inferred with `_`. Returns `None` if the whole type is
+    /// inferred.
+    pub fn closure_param_ty(&self, ty: &syn::Type) -> Option<TokenStream> {
+        if let Some(ty) = self.call_site_ty(ty) {
+            return Some(ty);
+        }
+        let inferred = |ty: &syn::Type| self.closure_param_ty(ty).unwrap_or_else(|| quote!( _ ));
+        match ty {
+            syn::Type::Reference(reference) => {
+                let mutability = &reference.mutability;
+                let elem = inferred(&reference.elem);
+                Some(quote!( & #mutability #elem ))
+            }
+            syn::Type::Ptr(ptr) => {
+                let (constness, mutability) = (&ptr.const_token, &ptr.mutability);
+                let elem = inferred(&ptr.elem);
+                Some(quote!( * #constness #mutability #elem ))
+            }
+            syn::Type::Slice(slice) => {
+                let elem = inferred(&slice.elem);
+                Some(quote!( [#elem] ))
+            }
+            syn::Type::Tuple(tuple) => {
+                let elems = tuple.elems.iter().map(inferred);
+                Some(quote!( ( #( #elems, )* ) ))
+            }
+            syn::Type::Paren(paren) => self.closure_param_ty(&paren.elem),
+            syn::Type::Group(group) => self.closure_param_ty(&group.elem),
+            _ => None,
+        }
+    }
+
+    fn call_site_tokens(&self, tokens: TokenStream) -> Option<TokenStream> {
+        let mut output = Vec::new();
+        let mut after_apostrophe = false;
+        for token in tokens {
+            let token = match token {
+                TokenTree::Group(group) => {
+                    let stream = self.call_site_tokens(group.stream())?;
+                    let mut new = proc_macro2::Group::new(group.delimiter(), stream);
+                    new.set_span(group.span());
+                    TokenTree::Group(new)
+                }
+                TokenTree::Ident(ref ident) if after_apostrophe => {
+                    if ident == "static" {
+                        token
+                    } else {
+                        TokenTree::Ident(syn::Ident::new("_", ident.span()))
+                    }
+                }
+                TokenTree::Ident(ref ident) => {
+                    // Generic parameters can shadow the primitive types.
+                    let keyword = ["str", "mut", "const", "fn"].iter().any(|name| ident == name);
+                    let primitive = PRIMITIVES.iter().any(|name| ident == name);
+                    if !(keyword || primitive) || self.names.contains(&ident.to_string()) {
+                        return None;
+                    }
+                    token
+                }
+                token => token,
+            };
+            after_apostrophe = match token {
+                TokenTree::Punct(ref punct) => punct.as_char() == '\'',
+                _ => false,
+            };
+            output.push(token);
+        }
+        Some(output.into_iter().collect())
+    }
+}
+
+/// The hidden wrapper of a function, `__fancyargs_connect` for `connect`.
+///
+/// The wrapper takes the arguments with hoisted defaults (see `hoisted()`)
+/// as `Option`, computes the omitted ones in the scope of the definition and
+/// calls the function. The wrapper of a method or associated function is an
+/// associated function of the same type. The wrapper of a free function `f`
+/// is an associated function of a hidden struct `f`, which is glob-imported
+/// next to the function, so that importing `f` imports it as well. A module
+/// or type `f` in the same scope shadows the struct, see `build_fn()`.
+pub fn wrapper_ident(fn_ident: &syn::Ident) -> syn::Ident {
+    syn::Ident::new(
+        &format!("__fancyargs_{}", fn_ident.to_string().trim_start_matches("r#")),
+        fn_ident.span(),
+    )
+}
+
+/// The path of the wrapper at the call site, from the path of the function
+/// (`connect` or `Client::connect`).
+pub fn wrapper_path(fn_path: &syn::Path) -> syn::Path {
+    let mut path = fn_path.clone();
+    let fn_ident = path.segments.last().unwrap().value().ident.clone();
+    let wrapper = wrapper_ident(&fn_ident);
+    if path.segments.len() == 1 {
+        path.segments.push(wrapper.into());
+    } else {
+        path.segments.pop();
+        path.segments.push(wrapper.into());
+    }
+    path
+}
+
+/// Whether the default of each argument is computed by the wrapper of the
+/// function, see `wrapper_ident()`.
+///
+/// Literals mean the same everywhere and `default` is an absolute path, so
+/// they stay at the call site, as well as the defaults of `impl Trait`
+/// parameters, whose type could not be inferred from `None`. Defaults at
+/// the call site can not refer to hoisted ones. Const functions have no
+/// wrapper, since it could not drop the `Option` in a const context.
+pub fn hoisted(args: &[ArgCaptured], constness: bool) -> Vec<bool> {
+    let mut hoisted = args
+        .iter()
+        .map(|arg| match arg.default {
+            Some(ref default) => {
+                let impl_trait = contains_ident(arg.ty.clone().into_token_stream(), "impl");
+                !constness && default.default_token.is_none() && !is_literal(&default.value) && !impl_trait
+            }
+            None => false,
+        })
+        .collect::<Vec<_>>();
+    // References only point at earlier arguments.
+    let names = param_names(args);
+    for index in (0..args.len()).rev() {
+        if !hoisted[index] {
+            for (referenced, _) in default_refs(&args[index], &names) {
+                hoisted[referenced] = false;
+            }
+        }
+    }
+    hoisted
+}
+
+/// Whether the tokens contain the identifier, also inside of groups.
+fn contains_ident(tokens: TokenStream, name: &str) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Group(group) => contains_ident(group.stream(), name),
+        TokenTree::Ident(ident) => ident == name,
+        _ => false,
+    })
+}
+
+/// Whether the expression is a (negated) literal.
+fn is_literal(expr: &syn::Expr) -> bool {
+    match expr {
+        syn::Expr::Lit(_) => true,
+        syn::Expr::Unary(syn::ExprUnary {
+            op: syn::UnOp::Neg(_),
+            expr,
+            ..
+        }) => is_literal(expr),
+        _ => false,
+    }
+}
+
+/// The primitive types that are `Copy`, which are the numbers, `bool` and
+/// `char`.
+const PRIMITIVES: &[&str] = &[
+    "bool", "char", "f32", "f64", "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128",
+    "usize",
+];
+
+/// The generic type arguments of a path type like `Option<T>` or
+/// `HashMap<K, V>`.
+fn generic_tys(ty: &syn::Type) -> Vec<&syn::Type> {
+    let path = match ty {
+        syn::Type::Path(path) => path,
+        _ => return Vec::new(),
+    };
+    let segment = match path.path.segments.last() {
+        Some(segment) => segment.into_value(),
+        None => return Vec::new(),
+    };
+    match segment.arguments {
+        syn::PathArguments::AngleBracketed(ref args) => args
+            .args
+            .iter()
+            .filter_map(|arg| match arg {
+                syn::GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The single generic type argument of a path type like `Option<T>` or
+/// `Vec<T>`.
+fn inner_ty(ty: &syn::Type) -> Option<&syn::Type> {
+    match generic_tys(ty).as_slice() {
+        [inner] => Some(inner),
+        _ => None,
+    }
+}
+
+/// How the elements of a vararg are passed.
+enum VarargKind {
+    /// Collected into a `FromIterator` type.
+    Collection,
+    /// `&[T]` or `&mut [T]`.
+    Slice(Option<syn::token::Mut>),
+    /// `[T; N]`.
+    Array,
+    /// `impl IntoIterator<Item = T>` or `impl Iterator<Item = T>`.
+    Iter,
+}
+
+/// The `Item` type of an `impl IntoIterator<Item = T>` or
+/// `impl Iterator<Item = T>` type.
+///
+/// The outer `Option` is `None` if the type is not an iterator, the inner one
+/// if the item type is not specified.
+fn iter_item_ty(ty: &syn::TypeImplTrait) -> Option<Option<&syn::Type>> {
+    ty.bounds.iter().find_map(|bound| {
+        let segment = match bound {
+            syn::TypeParamBound::Trait(bound) => bound.path.segments.last()?.into_value(),
+            _ => return None,
+        };
+        if segment.ident != "IntoIterator" && segment.ident != "Iterator" {
+            return None;
+        }
+        let item = match segment.arguments {
+            syn::PathArguments::AngleBracketed(ref args) => {
+                args.args.iter().find_map(|arg| match arg {
+                    syn::GenericArgument::Binding(binding) if binding.ident == "Item" => {
+                        Some(&binding.ty)
+                    }
+                    _ => None,
+                })
+            }
+            _ => None,
+        };
+        Some(item)
+    })
+}
+
+fn vararg_kind(ty: &syn::Type) -> VarargKind {
+    match ty {
+        syn::Type::Reference(reference) => match *reference.elem {
+            syn::Type::Slice(_) => VarargKind::Slice(reference.mutability),
+            _ => VarargKind::Collection,
+        },
+        syn::Type::Array(_) => VarargKind::Array,
+        syn::Type::ImplTrait(ty) if iter_item_ty(ty).is_some() => VarargKind::Iter,
+        _ => VarargKind::Collection,
+    }
+}
+
+/// The element type of a vararg, if it is known.
+///
+/// That is the element type of slices, arrays and iterators, the item type
+/// of the standard collections, and the element type of `Box<[T]>`-like and
+/// `SmallVec<[T; N]>`-like types. Other collections only rely on their
+/// `FromIterator` implementation.
+fn vararg_element_ty(ty: &syn::Type) -> Option<&syn::Type> {
+    const COLLECTIONS: &[&str] = &["Vec", "VecDeque", "LinkedList", "HashSet", "BTreeSet", "BinaryHeap"];
+    match ty {
+        syn::Type::Reference(reference) => {
+            return match *reference.elem {
+                syn::Type::Slice(ref slice) => Some(&slice.elem),
+                _ => None,
+            }
+        }
+        syn::Type::Array(array) => return Some(&array.elem),
+        syn::Type::ImplTrait(ty) => return iter_item_ty(ty).and_then(|item| item),
+        _ => {}
+    }
+    let inner = inner_ty(ty)?;
+    match inner {
+        syn::Type::Slice(slice) => Some(&slice.elem),
+        syn::Type::Array(array) => Some(&array.elem),
+        _ => match ty {
+            syn::Type::Path(path) => {
+                let ident = &path.path.segments.last()?.into_value().ident;
+                if COLLECTIONS.iter().any(|name| ident == name) {
+                    Some(inner)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        },
+    }
+}
+
+/// The value type of a map type like `HashMap<K, V>`.
+fn map_value_ty(ty: &syn::Type) -> Option<&syn::Type> {
+    match generic_tys(ty).as_slice() {
+        [_, value] => Some(value),
+        _ => None,
+    }
+}
+
+/// The type that the supplied values of the argument have: the element type
+/// of varargs, the value type of kwargs and the inner type of `Option<T>`
+/// for optional arguments.
+pub fn ascription_ty(def: &ArgCaptured) -> Option<&syn::Type> {
+    if def.is_vararg() {
+        vararg_element_ty(&def.ty)
+    } else if def.is_kwargs() {
+        map_value_ty(&def.ty)
+    } else if def.is_optional() && !def.is_optional_type() {
+        inner_ty(&def.ty)
+    } else {
+        Some(&def.ty)
+    }
+}
+
+/// Ties the expression to the given type, so that type errors point at the
+/// expression instead of the generated call.
+///
+/// The type is only written at the call site if it is built from primitive
+/// types, since all other names may not be in scope there. Function pointers
+/// are left out, because closures can only infer their parameter types if
+/// they are passed to the function directly. So are `&mut` references, which
+/// would be moved into the `match` instead of reborrowed like in a plain
+/// call.
+/// The value is bound with a `let` of the type, in a `match` so that its
+/// temporaries live as long as in a plain call. `span` is used for the type,
+/// usually the keyword or the expression itself.
+fn ascribe(generics: &Generics, ty: Option<&syn::Type>, expr: TokenStream, span: Span) -> TokenStream {
+    if let Some(syn::Type::Reference(syn::TypeReference { mutability: Some(_), .. })) = ty {
+        return expr;
+    }
+    let ty = match ty.and_then(|ty| generics.call_site_ty(ty)) {
+        Some(ty) => respan(ty, span),
+        None => return expr,
+    };
+    if contains_ident(ty.clone(), "fn") {
+        return expr;
+    }
+    let value = syn::Ident::new("__fancyargs_value", expr.span());
+    quote!(
+        match (#expr,) {
+            (#value,) => {
+                let #value: #ty = #value;
+                #value
+            }
+        }
+    )
+}
+
+/// Ties a supplied value of the argument to its `ascription_ty()`.
+fn ascribe_arg(def: &ArgCaptured, generics: &Generics, expr: TokenStream, span: Span) -> TokenStream {
+    ascribe(generics, ascription_ty(def), expr, span)
+}
+
+/// Converts a supplied value with `Into::into` if the argument asks for it.
+fn into(def: &ArgCaptured, expr: TokenStream, span: Span) -> TokenStream {
+    if def.is_into() {
+        quote_spanned!(span=> ::core::convert::Into::into(#expr) )
+    } else {
+        expr
+    }
+}
+
+/// Expression for an argument that was supplied in the macro call.
+///
+/// A vararg passed by keyword is the whole collection, only its elements
+/// are converted with `Into::into`.
+pub fn supplied(def: &ArgCaptured, generics: &Generics, expr: TokenStream, span: Span) -> TokenStream {
+    let expr = if def.is_vararg() { expr } else { into(def, expr, span) };
+    if def.is_optional_type() {
+        let expr = quote_spanned!(span=> ::core::convert::From::from(#expr) );
+        ascribe_arg(def, generics, expr, span)
+    } else if def.is_optional() {
+        let expr = ascribe_arg(def, generics, expr, span);
+        quote!( ::core::option::Option::Some(#expr) )
+    } else if def.is_vararg() || def.is_kwargs() {
+        ascribe(generics, Some(&def.ty), expr, span)
+    } else {
+        ascribe_arg(def, generics, expr, span)
+    }
+}
+
+/// The value that is passed to the wrapper for a supplied argument whose
+/// default is hoisted, see `hoisted()`.
+pub fn hoisted_supplied(expr: TokenStream) -> TokenStream {
+    quote!( ::core::option::Option::Some(#expr) )
+}
+
+/// Expression for an argument that was omitted in the macro call.
+///
+/// The default is computed by the wrapper if it is `hoisted`.
+/// Returns `None` if the argument is required.
+pub fn omitted(def: &ArgCaptured, hoisted: bool) -> Option<TokenStream> {
+    if hoisted {
+        Some(quote!( ::core::option::Option::None ))
+    } else if def.is_optional_type() {
+        Some(quote!( ::core::default::Default::default() ))
+    } else if def.is_optional() {
+        Some(quote!( ::core::option::Option::None ))
+    } else {
+        def.default().map(|default_expr| quote!( #default_expr ))
+    }
+}
+
+/// Error message for a required argument that was omitted.
+///
+/// `index` is the position of the argument in the macro call.
+pub fn missing(def: &ArgCaptured, index: usize) -> String {
+    match def.name() {
+        Some(name) => format!("Missing required argument '{}'", name),
+        None => format!("Missing required positional argument {}", index + 1),
+    }
+}
+
+/// Error message for a flag (`name` or `!name`) of an argument that was
+/// already passed.
+pub fn duplicate_flag(name: &str) -> String {
+    format!(
+        "Duplicate keyword argument '{0}': '{0}' is a flag for `{0} = true`, write `({0})` to pass a variable positionally",
+        name
+    )
+}
+
+/// Error message for a flag (`name` or `!name`) at a position where a
+/// positional argument can be passed, so it could also be a variable.
+pub fn ambiguous_flag(name: &str, value: bool) -> String {
+    let not = if value { "" } else { "!" };
+    format!(
+        "Ambiguous argument '{1}{0}': write `{0} = {1}{0}` to pass a variable or `{0} = {2}` to set the flag",
+        name, not, value
+    )
+}
+
+/// Wraps the expression of an argument that was passed with a deprecated
+/// alias, so that the call site gets a deprecation warning.
+///
+/// The warning points at `keyword`, the keyword at the call site.
+/// The expression is not moved into a block, so its temporaries live as
+/// long as in a plain call.
+pub fn deprecated_alias(def: &ArgCaptured, alias: &Alias, expr: TokenStream, keyword: TokenStream) -> TokenStream {
+    let span = keyword.span();
+    // Cloned rather than created from the string, which panics for raw
+    // identifiers like `r#type`.
+    let mut name = alias.name.clone();
+    name.set_span(span);
+    let note = match alias.note {
+        Some(ref note) => format!("the keyword argument '{}' is deprecated: {}", name, note.value()),
+        None => format!(
+            "the keyword argument '{}' is deprecated, use '{}' instead",
+            name,
+            def.name().unwrap_or_default()
+        ),
+    };
+    quote_spanned!(span=> (
+        {
+            #[deprecated(note = #note)]
+            #[allow(non_camel_case_types)]
+            struct #name;
+            let _ = #keyword;
+        },
+        #expr,
+    ).1)
+}
+
+/// A type as it is shown in messages and docs.
+pub fn display_ty(ty: &syn::Type) -> String {
+    display_tokens(quote!( #ty ), |ty| {
+        [(" <", "<"), ("< ", "<"), (" >", ">"), ("& ", "&")]
+            .iter()
+            .fold(ty, |ty, (from, to)| ty.replace(from, to))
+    })
+}
+
+/// A default value as it is shown in docs, `format!("{}", a.len())` rather
+/// than `format! ("{}", a . len ())`.
+pub fn display_expr(expr: &syn::Expr) -> String {
+    display_tokens(quote!( #expr ), |expr| {
+        let expr = [(" . ", "."), (" .. ", ".."), (" ..= ", "..="), ("( ", "("), (" )", ")"), ("[ ", "["), (" ]", "]")]
+            .iter()
+            .fold(expr, |expr, (from, to)| expr.replace(from, to));
+        // Calls, indexing, macros, unary operators and the generics of paths
+        // are printed with spaces as well.
+        let operand = |ch: char| ch.is_alphanumeric() || "_)]}".contains(ch);
+        let mut display = String::new();
+        let mut generics = 0;
+        let mut chars = expr.chars().peekable();
+        while let Some(ch) = chars.next() {
+            let next = chars.peek().cloned().unwrap_or(' ');
+            let last = display.chars().rev().collect::<Vec<_>>();
+            let callee = match last.first() {
+                Some('>') => generics == 0,
+                Some(&last) => operand(last) || last == '!',
+                None => false,
+            };
+            let unary = match last.as_slice() {
+                [op] => "!-&*".contains(*op),
+                [op, ' ', before, ..] => "!-&*".contains(*op) && !operand(*before),
+                [op, before, ..] => "!-&*".contains(*op) && "!-&*".contains(*before) && op != before,
+                _ => false,
+            };
+            let in_generics = generics > 0 && ("<>".contains(next) || last.first() == Some(&'<'));
+            if ch == ' ' && ((callee && "([".contains(next)) || unary || in_generics) {
+                continue;
+            }
+            display.push(ch);
+            match ch {
+                '<' if generics > 0 || display.ends_with("::<") => generics += 1,
+                '>' if generics > 0 => generics -= 1,
+                _ => (),
+            }
+        }
+        display
+    })
+}
+
+/// Prints tokens without the spaces that are printed between all of them,
+/// `tidy` removes the spaces specific to types or expressions.
+///
+/// Literals are replaced by placeholders meanwhile, to keep the spaces in
+/// strings.
+fn display_tokens(tokens: TokenStream, tidy: impl FnOnce(String) -> String) -> String {
+    fn hide_literals(tokens: TokenStream, literals: &mut Vec<String>) -> TokenStream {
+        let mut hide = |tt| match tt {
+            TokenTree::Literal(lit) => {
+                literals.push(lit.to_string());
+                let placeholder = format!("__fancyargs_literal{}_", literals.len() - 1);
+                TokenTree::Ident(syn::Ident::new(&placeholder, lit.span()))
+            }
+            TokenTree::Group(group) => {
+                let stream = hide_literals(group.stream(), literals);
+                TokenTree::Group(proc_macro2::Group::new(group.delimiter(), stream))
+            }
+            tt => tt,
+        };
+        tokens.into_iter().map(&mut hide).collect()
+    }
+    let mut literals = Vec::new();
+    // Long token streams are printed on several lines.
+    let tokens = hide_literals(tokens, &mut literals).to_string().split_whitespace().collect::<Vec<_>>().join(" ");
+    let display = [(" :: ", "::"), (":: ", "::"), (" ,", ","), (" ;", ";")]
+        .iter()
+        .fold(tokens, |tokens, (from, to)| tokens.replace(from, to));
+    literals.iter().enumerate().fold(tidy(display), |display, (index, lit)| {
+        display.replace(&format!("__fancyargs_literal{}_", index), lit)
+    })
+}
+
+/// The signature of the function in the custom syntax, for error messages.
+///
+/// Default values are shown as `= ..`.
+pub fn signature(name: &str, receiver: bool, args: &[ArgCaptured]) -> String {
+    let mut params = Vec::new();
+    if receiver {
+        params.push("self".to_string());
+    }
+    let last_pos_only = args.iter().rposition(ArgCaptured::is_pos_only);
+    let first_kw_only = args.iter().position(|arg| arg.is_kw_only() && !arg.is_vararg());
+    for (index, arg) in args.iter().enumerate() {
+        if Some(index) == first_kw_only && !args[..index].iter().any(ArgCaptured::is_vararg) {
+            params.push("*".to_string());
+        }
+        let pat = arg.binding_pat();
+        let name = arg.name().unwrap_or_else(|| quote!( #pat ).to_string());
+        let marker = if arg.is_kwargs() {
+            "**"
+        } else if arg.is_vararg() {
+            "*"
+        } else {
+            ""
+        };
+        let into = if arg.is_into() { "~" } else { "" };
+        let optional = if arg.is_optional() { "?" } else { "" };
+        let default = if arg.default.is_some() { " = .." } else { "" };
+        params.push(format!("{}{}{}{}: {}{}", name, marker, into, optional, display_ty(&arg.ty), default));
+        if Some(index) == last_pos_only {
+            params.push("/".to_string());
+        }
+    }
+    format!("{}({})", name, params.join(", "))
+}
+
+/// The arguments at the `positional` indices, for error messages that say
+/// which arguments were already passed positionally.
+///
+/// Returns an empty string if there are none.
+pub fn passed_positionally(args: &[ArgCaptured], receiver: bool, positional: &[usize]) -> String {
+    if positional.is_empty() {
+        return String::new();
+    }
+    let names = positional
+        .iter()
+        .map(|index| match args[*index].name() {
+            Some(name) => format!("'{}'", name),
+            None => format!("argument {}", index + 1 + receiver as usize),
+        })
+        .collect::<Vec<_>>();
+    format!("\n  passed positionally: {}", names.join(", "))
+}
+
+/// Number of single character edits to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous + (a != *b) as usize;
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(previous + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// The keyword that is most similar to the unknown keyword `name`, if there
+/// is one that is close enough to be a typo.
+pub fn closest_keyword(name: &str, args: &[ArgCaptured]) -> Option<String> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    args.iter()
+        .filter(|arg| !arg.is_pos_only() && !arg.is_kwargs())
+        .filter_map(ArgCaptured::name)
+        .map(|keyword| (edit_distance(name, &keyword), keyword))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, keyword)| keyword)
+}
+
+/// Misspellings of a keyword that are one edit away from it: a missing
+/// letter, two swapped letters or a doubled letter.
+///
+/// The declarative macros can not compute `closest_keyword()` at the call
+/// site, so they match these instead.
+pub fn typos(keyword: &str) -> Vec<String> {
+    let chars = keyword.trim_start_matches("r#").chars().collect::<Vec<_>>();
+    let mut typos = Vec::new();
+    for index in 0..chars.len() {
+        let mut missing = chars.clone();
+        missing.remove(index);
+        typos.push(missing);
+        let mut doubled = chars.clone();
+        doubled.insert(index, chars[index]);
+        typos.push(doubled);
+        if index + 1 < chars.len() && chars[index] != chars[index + 1] {
+            let mut swapped = chars.clone();
+            swapped.swap(index, index + 1);
+            typos.push(swapped);
+        }
+    }
+    let mut unique: Vec<String> = Vec::new();
+    for typo in typos {
+        let typo = typo.into_iter().collect::<String>();
+        if syn::parse_str::<syn::Ident>(&typo).is_ok() && !unique.contains(&typo) {
+            unique.push(typo);
+        }
+    }
+    unique
+}
+
+/// Expression for a single element of a vararg argument.
+pub fn vararg_element(def: &ArgCaptured, generics: &Generics, expr: TokenStream, span: Span) -> TokenStream {
+    ascribe_arg(def, generics, into(def, expr, span), span)
+}
+
+/// Expression for a vararg argument, from the given comma separated
+/// elements.
+///
+/// Slices, arrays and iterators are built from an array, so they don't
+/// require an allocation. Other types are collected with `FromIterator`.
+pub fn vararg(def: &ArgCaptured, elements: TokenStream) -> TokenStream {
+    match vararg_kind(&def.ty) {
+        VarargKind::Collection => quote!(
+            ::core::iter::Iterator::collect(::core::iter::IntoIterator::into_iter([ #elements ]))
+        ),
+        VarargKind::Slice(mutability) => quote!( &#mutability [ #elements ] ),
+        VarargKind::Array => quote!( [ #elements ] ),
+        VarargKind::Iter => quote!( ::core::iter::IntoIterator::into_iter([ #elements ]) ),
+    }
+}
+
+/// The iterator of a spread vararg element (`..iter`).
+///
+/// A parenthesized range (`(..end)`) is passed as regular element.
+pub fn spread(expr: &syn::Expr) -> Option<&syn::Expr> {
+    match expr {
+        syn::Expr::Range(syn::ExprRange {
+            attrs,
+            from: None,
+            limits: syn::RangeLimits::HalfOpen(_),
+            to: Some(iter),
+        }) if attrs.is_empty() => Some(iter),
+        _ => None,
+    }
+}
+
+/// Iterator over the given comma separated vararg elements, used as part
+/// of a spread vararg.
+pub fn vararg_elements_iter(elements: TokenStream) -> TokenStream {
+    quote!( ::core::iter::IntoIterator::into_iter([ #elements ]) )
+}
+
+/// Iterator of a spread element, used as part of a spread vararg.
+///
+/// The items are converted with `Into::into` if the vararg asks for it.
+pub fn vararg_spread_iter(def: &ArgCaptured, generics: &Generics, iter: TokenStream) -> TokenStream {
+    let iter = quote!( ::core::iter::IntoIterator::into_iter(#iter) );
+    if !def.is_into() {
+        return iter;
+    }
+    match vararg_element_ty(&def.ty).and_then(|ty| generics.call_site_ty(ty)) {
+        Some(ty) => quote!( ::core::iter::Iterator::map(#iter, ::core::convert::Into::<#ty>::into) ),
+        None => quote!( ::core::iter::Iterator::map(#iter, ::core::convert::Into::into) ),
+    }
+}
+
+/// Expression for a vararg argument that contains spread elements, from
+/// the iterators of its parts, which are chained in order.
+///
+/// Returns an error message if the vararg can not be built from an
+/// iterator (slices and arrays).
+pub fn vararg_chain(def: &ArgCaptured, parts: Vec<TokenStream>) -> Result<TokenStream, String> {
+    let chain = parts
+        .into_iter()
+        .fold(None, |chain, part| match chain {
+            Some(chain) => Some(quote!( ::core::iter::Iterator::chain(#chain, #part) )),
+            None => Some(part),
+        })
+        .unwrap_or_else(|| vararg_elements_iter(quote!()));
+    match vararg_kind(&def.ty) {
+        VarargKind::Collection => Ok(quote!( ::core::iter::Iterator::collect(#chain) )),
+        VarargKind::Iter => Ok(chain),
+        VarargKind::Slice(_) | VarargKind::Array => Err(format!(
+            "Invalid spread argument: vararg '{}' is a slice or array, which can not be built from an iterator",
+            def.name().unwrap_or_default()
+        )),
+    }
+}
+
+/// Expression for a single `name = expr` entry of a kwargs argument.
+///
+/// The key is the keyword as string, without the `r#` prefix of raw
+/// identifiers.
+pub fn kwargs_entry(
+    def: &ArgCaptured,
+    generics: &Generics,
+    name: &syn::Ident,
+    expr: TokenStream,
+    span: Span,
+) -> TokenStream {
+    let key = name.to_string();
+    let key = syn::LitStr::new(key.trim_start_matches("r#"), name.span());
+    let value = ascribe_arg(def, generics, expr, span);
+    quote!( (#key, #value) )
+}
+
+/// Expression for a kwargs argument, collected from the given comma
+/// separated entries.
+pub fn kwargs(entries: TokenStream) -> TokenStream {
+    quote!(
+        ::core::iter::Iterator::collect(::core::iter::IntoIterator::into_iter([ #entries ]))
+    )
+}
+
+/// Names of the arguments that default values can refer to.
+///
+/// Only plain identifier patterns can be referred to, the entry is `None`
+/// for all other arguments.
+pub fn param_names(args: &[ArgCaptured]) -> Vec<Option<syn::Ident>> {
+    args.iter()
+        .map(|arg| match arg.pat {
+            syn::Pat::Ident(ref pat) if pat.subpat.is_none() => Some(pat.ident.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The identifier that the value of the argument at `index` is bound to,
+/// when the arguments are bound before the call.
+pub fn binding(names: &[Option<syn::Ident>], index: usize) -> syn::Ident {
+    match names[index] {
+        Some(ref name) => name.clone(),
+        None => syn::Ident::new(&format!("__fancyargs_arg{}", index), Span::call_site()),
+    }
+}
+
+/// Replaces all identifiers in `tokens` that may refer to a local variable
+/// with the tokens returned by `f`, if any.
+///
+/// Identifiers that are part of a path, a field access, a macro call or
+/// are followed by a `:` (struct fields) are skipped.
+fn replace_vars<F>(tokens: TokenStream, f: &mut F) -> TokenStream
+where
+    F: FnMut(&syn::Ident) -> Option<TokenStream>,
+{
+    let is_punct = |token: Option<&TokenTree>, ch: char, spacing: Option<Spacing>| match token {
+        Some(TokenTree::Punct(punct)) => {
+            punct.as_char() == ch && spacing.map(|s| punct.spacing() == s).unwrap_or(true)
+        }
+        _ => false,
+    };
+    let tokens = tokens.into_iter().collect::<Vec<_>>();
+    let mut output = TokenStream::new();
+    for (index, token) in tokens.iter().enumerate() {
+        let prev = |offset: usize| index.checked_sub(offset).map(|i| &tokens[i]);
+        match token {
+            TokenTree::Group(group) => {
+                let stream = replace_vars(group.stream(), f);
+                let mut new = proc_macro2::Group::new(group.delimiter(), stream);
+                new.set_span(group.span());
+                output.extend(Some(TokenTree::Group(new)));
+                continue;
+            }
+            TokenTree::Ident(ident) => {
+                let next = tokens.get(index + 1);
+                let field = is_punct(prev(1), '.', None)
+                    && !is_punct(prev(2), '.', Some(Spacing::Joint));
+                let path = is_punct(prev(1), ':', None) && is_punct(prev(2), ':', Some(Spacing::Joint));
+                let lifetime = is_punct(prev(1), '\'', None);
+                let skip = field
+                    || path
+                    || lifetime
+                    || is_punct(next, ':', None)
+                    || is_punct(next, '!', None);
+                if !skip {
+                    if let Some(replacement) = f(ident) {
+                        output.extend(replacement);
+                        continue;
+                    }
+                }
+            }
+            _ => {}
+        }
+        output.extend(Some(token.clone()));
+    }
+    output
+}
+
+/// Sets the span of all tokens, so that the identifiers resolve at `span`.
+pub fn respan(tokens: TokenStream, span: Span) -> TokenStream {
+    tokens
+        .into_iter()
+        .map(|token| match token {
+            TokenTree::Group(group) => {
+                let mut new = proc_macro2::Group::new(group.delimiter(), respan(group.stream(), span));
+                new.set_span(span);
+                TokenTree::Group(new)
+            }
+            mut token => {
+                token.set_span(span);
+                token
+            }
+        })
+        .collect()
+}
+
+/// References to other arguments in the default value of `def`, with the
+/// index of the referenced argument.
+pub fn default_refs(def: &ArgCaptured, names: &[Option<syn::Ident>]) -> Vec<(usize, syn::Ident)> {
+    let mut refs = Vec::new();
+    if let Some(default) = def.default() {
+        replace_vars(default.into_token_stream(), &mut |ident| {
+            if let Some(index) = names.iter().position(|name| name.as_ref() == Some(ident)) {
+                refs.push((index, ident.clone()));
+            }
+            None
+        });
+    }
+    refs
+}
+
+/// Replaces the references to other arguments in `expr` with the tokens
+/// returned by `f` for the index of the referenced argument.
+pub fn replace_refs<F>(expr: TokenStream, names: &[Option<syn::Ident>], mut f: F) -> TokenStream
+where
+    F: FnMut(usize) -> TokenStream,
+{
+    replace_vars(expr, &mut |ident| {
+        names
+            .iter()
+            .position(|name| name.as_ref() == Some(ident))
+            .map(&mut f)
+    })
+}
+
+/// Whether the value of the expression does not depend on when it is
+/// evaluated.
+///
+/// Paths are not included, since an argument passed later can modify the
+/// variable. Closures are included, because their parameter types can only
+/// be inferred if they are passed to the function directly.
+fn is_pure(expr: &syn::Expr) -> bool {
+    matches!(expr, syn::Expr::Lit(_) | syn::Expr::Closure(_))
+}
+
+/// Whether the supplied value of the argument is a `&mut` reference, which a
+/// plain call reborrows instead of moving it.
+pub fn reborrowed(def: &ArgCaptured) -> bool {
+    let plain = !(def.is_into() || def.is_optional() || def.is_vararg() || def.is_kwargs());
+    plain && matches!(def.ty, syn::Type::Reference(syn::TypeReference { mutability: Some(_), .. }))
+}
+
+/// Expressions of the macro call that are bound to temporaries before the
+/// call, so they are evaluated in the order they were passed even if the
+/// arguments are reordered.
+#[derive(Default)]
+pub struct Temporaries {
+    /// The position in the macro call, the temporary and the expression.
+    /// The receiver has position 0, the arguments start at 1.
+    values: Vec<(usize, syn::Ident, TokenStream)>,
+    /// Whether all expressions are bound, for partial application.
+    all: bool,
+}
+
+impl Temporaries {
+    /// Binds all expressions, including the receiver, so they are
+    /// evaluated once before the call is made.
+    pub fn all() -> Self {
+        Temporaries {
+            values: Vec::new(),
+            all: true,
+        }
+    }
+
+    /// Tokens for the expression at `index` in the macro call.
+    ///
+    /// Returns a placeholder that is resolved by `finish()`, or the
+    /// expression itself if it has no side effects.
+    pub fn bind(&mut self, index: usize, expr: &syn::Expr) -> TokenStream {
+        self.bind_at(index + 1, expr, quote!( #expr ))
+    }
+
+    /// Tokens for the expression at `index` in the macro call, which is
+    /// passed for the argument `def`. A `&mut` argument is reborrowed rather
+    /// than moved into the temporary, like in a plain call.
+    pub fn bind_arg(&mut self, index: usize, def: &ArgCaptured, expr: &syn::Expr) -> TokenStream {
+        if reborrowed(def) {
+            self.bind_at(index + 1, expr, quote!( &mut *(#expr) ))
+        } else {
+            self.bind(index, expr)
+        }
+    }
+
+    /// Tokens for the receiver of a method, which is evaluated before the
+    /// arguments. It is bound by `borrow`, so it is not moved for `&self`
+    /// and `&mut self` methods. A variable has nothing to evaluate, so it is
+    /// left in place unless all expressions are bound.
+    pub fn bind_receiver(&mut self, expr: &syn::Expr, borrow: TokenStream) -> TokenStream {
+        if let (syn::Expr::Path(_), false) = (expr, self.all) {
+            return quote!( #expr );
+        }
+        let temp = syn::Ident::new("__fancyargs_tmp0", expr.span());
+        self.values.push((0, temp.clone(), quote!( #borrow (#expr) )));
+        quote!( #temp )
+    }
+
+    fn bind_at(&mut self, position: usize, expr: &syn::Expr, value: TokenStream) -> TokenStream {
+        if is_pure(expr) && !self.all {
+            return quote!( #expr );
+        }
+        let temp = syn::Ident::new(&format!("__fancyargs_tmp{}", position), expr.span());
+        self.values.push((position, temp.clone(), value));
+        quote!( #temp )
+    }
+
+    /// Resolves the placeholders in `call`.
+    ///
+    /// If the call evaluates the expressions in the order they were passed,
+    /// they are put back in place, so temporaries and borrows behave like in
+    /// a plain call. Otherwise they are bound in a `match` first, which
+    /// keeps temporaries alive until the end of the call.
+    pub fn finish(mut self, call: TokenStream) -> TokenStream {
+        let mut order = Vec::new();
+        let call = replace_vars(call, &mut |ident| {
+            let position = self.values.iter().position(|(_, temp, _)| temp == ident)?;
+            order.push(self.values[position].0);
+            None
+        });
+        if !self.all && order.windows(2).all(|pair| pair[0] < pair[1]) {
+            let values = &self.values;
+            return replace_vars(call, &mut |ident| {
+                values
+                    .iter()
+                    .find(|(_, temp, _)| temp == ident)
+                    .map(|(_, _, expr)| expr.clone())
+            });
+        }
+        self.values.sort_by_key(|(index, _, _)| *index);
+        let temps = self.values.iter().map(|(_, temp, _)| temp);
+        let exprs = self.values.iter().map(|(_, _, expr)| expr);
+        quote!(
+            match ( #( #exprs, )* ) {
+                ( #( #temps, )* ) => #call,
+            }
+        )
+    }
+}
@@ -16,14 +16,79 @@
 //! * **Both the macro and the function name need to be in scope.**
 //! * When calling the macro, positional arguments may not follow keyword arguments.
 //! * You can specify multiple functions inside a single macro invocation.
+//! * Parameters can be patterns, which can only be passed positionally (this includes `_: T`).
+//!   A pattern can be named with `name @ pattern` (`point @ (x, y): (i32, i32)`) to pass it as keyword argument.
+//!   The name is only used as keyword and is not bound in the function body.
+//! * Parameters after a bare `*,` separator are keyword-only (`fn f(a: u8, *, b: u8 = 1)`).
+//!   Passing them positionally is a compile error, so they can be reordered without breaking callers.
+//! * Parameters before a `/` separator are positional-only (`fn f(a: u8, /, b: u8 = 1)`).
+//!   Their names are not part of the macro API and can be renamed freely.
+//! * Varargs typed as `&[T]`, `[T; N]` or `impl IntoIterator<Item = T>` are passed as a slice, an array or an
+//!   array iterator, which does not require an allocation (and works in `no_std` crates).
+//! * Existing iterators can be spread into a vararg with `..iter` (`roles!(..existing, "extra")`). The spread iterators
+//!   and the other elements are chained in order. Spreading requires a `FromIterator` or `impl IntoIterator` vararg.
+//!   To pass a `RangeTo` as a single element, wrap it in parentheses (`(..end)`).
+//! * A `name**: HashMap<&'static str, T>` (or `BTreeMap`) parameter collects all unmatched keyword arguments,
+//!   keyed by the keyword (`tag!("input", r#type = "text")` passes `{"type": "text"}`). It must be the last parameter.
+//! * Optional parameters can use any path to `Option` (`x?: std::option::Option<u8>`). For type aliases and other
+//!   types that implement `Default`, add `#[optional_type]`: an omitted argument is `Default::default()` and a
+//!   supplied one is converted with `From::from` (`#[optional_type] name: String` accepts `name = "x"`).
+//! * Generic parameters of the function can be set like keyword arguments (`parse!(T = u32, input)`, `N = 4` for const
+//!   generics). They can be given anywhere in the call, the others are inferred.
+//! * Parameters can have doc comments and `#[cfg(..)]` attributes. A parameter that is configured out is removed from
+//!   the function and from the macro. A macro is generated for every combination of `#[cfg]` parameters, so at most
+//!   four parameters can have `#[cfg]` attributes.
+//! * The generated macro is documented with the docs of the function and a table of its arguments, with their type,
+//!   kind, default value and doc comments.
+//! * Arguments are evaluated in the order they are passed, like in a plain call. If keyword arguments are passed in a
+//!   different order than the parameters, they are bound to temporaries first.
+//! * A `bool` parameter with a default value can be set with a flag after the keyword arguments:
+//!   `render!(tpl = "a", verbose)` is `verbose = true` and `render!(tpl = "a", !cache)` is `cache = false`. Where a
+//!   positional argument can still be passed, a flag could also be a variable, so `render!(tpl, verbose)` is an error
+//!   that asks for `verbose = verbose` or `verbose = true`. Parentheses pass the variable positionally (`render!(tpl, (verbose))`).
+//! * Supplied values of a parameter marked with `~` (`name~: String`) are converted with `Into::into`, so callers can
+//!   pass `&str`, `String` or `Cow<str>` while the function keeps its type. For varargs (`names*~: Vec<String>`) every
+//!   element is converted. Default values are not converted.
+//! * A parameter can be renamed without breaking callers: `#[alias(old_name)]` keeps accepting `old_name = value` and
+//!   warns about the deprecated name at the call site (`#[alias(old_name, deprecated = "note")]` sets the note).
+//! * `fn titled(title: &str, ..inherit(plot))` imports the parameters of the fancy function `plot` with their types,
+//!   defaults and markers, and `forward!(plot)` calls `plot` with them from the body (`forward!(draw, self)` for
+//!   methods). The parameters are requested from the macro of `plot` during the expansion, so it must be defined
+//!   before the wrapper and outside of its impl block, and their types and defaults must be nameable at the wrapper.
+//!   Only `fancyargs!()` supports the directive.
+//! * Errors for invalid calls suggest similar keyword names and show the signature of the function.
 //! 
+//! ## Defaults
+//!
+//! * Default values are resolved in the scope of the function definition. A hidden wrapper next to the function
+//!   computes them, only literals and the defaults of `impl Trait` parameters are passed at the call site.
+//!   A `const fn` has no wrapper, so all of its defaults are passed at the call site, where names resolve in the
+//!   scope of the caller.
+//! * Default values can refer to earlier parameters (`end: usize = start + 10`). The explicit arguments are
+//!   evaluated first, and the defaults are computed from their values in declaration order.
+//!
+//! ## Methods
+//!
+//! * Functions inside an inherent `impl` block are supported. Methods take the receiver as the first positional
+//!   argument (`client_connect!(client, timeout = 5)`), associated functions are called through the type.
+//! * Their macros are prefixed with the snake case type name, since names like `new` or `connect` are shared by many
+//!   types (`http_client_new!()` calls `HttpClient::new()`).
+//! * The receiver is always evaluated first. It is borrowed for `&self` and `&mut self` methods rather than moved.
+//!
+//! ## Partial application
+//!
+//! * `connect!(@partial host = "db", timeout = 5)` evaluates the given arguments and returns a closure that takes the
+//!   remaining required arguments positionally, in declaration order. Reference parameters accept a new borrow on
+//!   every call.
+//! * Omitted optional and default arguments are computed when the closure is called.
+//! * The values are moved into the closure and passed on by value, so the closure can only be called once (`FnOnce`)
+//!   unless they are all `Copy`. Pass references to borrow them, or to call the closure repeatedly.
+//! * The receiver of a method is borrowed like in a call. The closure of a `&mut self` method is `FnMut`, must be bound
+//!   with `let mut`, and keeps the receiver borrowed while it is alive.
+//!
 //!  ## Full example
 //!
 //!```rust
-//! // Nightly and the `proc_macro_hygiene` feature are required because proc 
-//! // macros can't yet produce expressions on stable.
-//! #![feature(proc_macro_hygiene)]
-//! 
 //! # extern crate fancyargs;
 //! 
 //! fancyargs::fancyargs!(
@@ -38,7 +103,8 @@
 //!         // Optional argument with a default value.
 //!         is_superuser: bool = false, 
 //!         // A vararg argument. 
-//!         // The macro accepts an arbitrary amount of trailing values which will be collected to a Vec.
+//!         // The macro accepts an arbitrary amount of trailing values which will be collected to a Vec
+//!         // (or any other collection that implements FromIterator).
 //!         // NOTE the * after the argument name.
 //!         roles*: Vec<&str>,
 //!     ) -> String {
@@ -67,198 +133,1343 @@
 //! }
 //!```
 //!
+//! ## Declarative macros
+//!
+//! By default the generated macro forwards to a proc macro that resolves the arguments.
+//! Adding `#[fancy(declarative)]` to a function or impl block generates a self-contained
+//! `macro_rules!` instead, which does not call a proc macro at the call site.
+//!
+//!```rust
+//! # extern crate fancyargs;
+//! fancyargs::fancyargs!(
+//!     #[fancy(declarative)]
+//!     fn greet(name: &str, greeting: &str = "Hello") -> String {
+//!         format!("{}, {}!", greeting, name)
+//!     }
+//! );
+//!
+//! fn main() {
+//!     assert_eq!(greet!("John"), "Hello, John!");
+//!     assert_eq!(greet!(greeting = "Hi", name = "John"), "Hi, John!");
+//! }
+//!```
+//!
+//! * Kwargs parameters are not supported, they are rejected with a compile error.
+//! * Errors for invalid calls only suggest keywords that are one missing, swapped or doubled letter away.
+//!
+//! ## Exporting from a library
+//!
+//! Macros of functions exported by a library call `::fancyargs::invoke!()`, so every crate that calls them would
+//! need a `fancyargs` dependency of its own. A library avoids that by re-exporting fancyargs at its root and pointing
+//! the `crate` option of its fancy functions at the re-export. Declarative macros only use it for `..inherit()`.
+//!
+//!```rust
+//! # pub extern crate fancyargs;
+//! #[doc(hidden)]
+//! pub use fancyargs as __fancyargs;
+//!
+//! fancyargs::fancyargs!(
+//!     #[fancy(crate = "$crate::__fancyargs")]
+//!     pub fn greet(name: &str, greeting: &str = "Hello") -> String {
+//!         format!("{}, {}!", greeting, name)
+//!     }
+//! );
+//!
+//! fn main() {
+//!     assert_eq!(greet!("John"), "Hello, John!");
+//! }
+//!```
+//!
 //!  ## Limitations
 //! 
-//!  * Nightly only due to the required [proc_macro_hygiene](https://doc.rust-lang.org/unstable-book/language-features/proc-macro-hygiene.html) feature.
-//! * Both the macro and the original function must be in scope.
-//!  * Must be a regular macro rather than a attribute, because attribute proc macros require valid Rust syntax.
-//!  * Compilation errors are not that great.
+//!  * The generated code requires Rust 1.53 or newer. The generated macros call a proc macro in expression position,
+//!    with `#[fancy(declarative)]` a self-contained `macro_rules!` is generated instead, see [Declarative macros](#declarative-macros).
+//! * Both the macro and the original function must be in scope, `use dep::connect;` imports both. The macro can then be
+//!   called by path too. A free function with computed defaults can not share its name with a module or type
+//!   in the same scope, which is reported at the definition as a missing `__fancyargs_name_is_taken_by_a_module_or_type`.
+//!  * The custom syntax requires a function-like macro, because attribute proc macros require valid Rust syntax.
+//!    The `#[fancy]` attribute with `#[default(..)]`, `#[optional]` and `#[vararg]` parameter attributes can be used instead.
+//!    It is named `#[fancy]` rather than `#[fancyargs]` because attribute and function-like macros share one namespace,
+//!    so a crate can not export both under the name `fancyargs`.
 //!
 
 extern crate proc_macro;
 
+mod declarative;
+mod error;
+mod expand;
 mod parse;
 
 use proc_macro::TokenStream;
-use proc_macro2::TokenStream as TokenStream2;
+use proc_macro2::{Span, TokenStream as TokenStream2};
 use quote::quote;
+use syn::spanned::Spanned;
+
+use crate::error::Errors;
 
-fn build_fn(ast: parse::ItemFn) -> TokenStream2 {
-    // Verify that function is not a method.
-    let last_index = ast.decl.inputs.len() - 1;
+/// The maximum number of parameters with `#[cfg]` attributes, a macro is
+/// generated for every combination of them.
+const MAX_CFG_PARAMS: usize = 4;
+
+/// The impl block a function is defined in.
+struct ImplContext<'a> {
+    /// Path of the implemented type, without generic arguments.
+    self_path: &'a syn::Path,
+    generics: &'a syn::Generics,
+}
 
-    let clean_args = ast
+/// Builds the macro for a single function.
+///
+/// Returns the cleaned up function definition, the hidden wrapper that
+/// computes the default values and the `macro_rules!` definition.
+/// `parent` is set if the function is defined inside an impl block, in which
+/// case the wrapper is an associated function that must be emitted inside of
+/// the impl block.
+/// If the function is invalid, the errors are added to `errors` and no macro
+/// is generated.
+fn build_fn(
+    mut ast: parse::ItemFn,
+    parent: Option<&ImplContext>,
+    options: &parse::Options,
+    errors: &mut Errors,
+) -> (syn::ItemFn, TokenStream2, TokenStream2) {
+    let mut fn_errors = Errors::default();
+
+    let options = match options.extract(&mut ast.attrs) {
+        Ok(options) => options,
+        Err(e) => {
+            fn_errors.push(e);
+            parse::Options::default()
+        }
+    };
+    let last_index = ast.decl.inputs.len().saturating_sub(1);
+    let kwargs_last = ast
         .decl
         .inputs
-        .iter()
-        .enumerate()
-        .map(|(index, arg)| match arg {
+        .last()
+        .and_then(|pair| pair.into_value().captured())
+        .map(parse::ArgCaptured::is_kwargs)
+        .unwrap_or(false);
+
+    for (index, arg) in ast.decl.inputs.iter().enumerate() {
+        match arg {
             parse::FnArg::SelfRef(_) | parse::FnArg::SelfValue(_) => {
-                panic!("fancyargs!() macro may not be used on methods, only standalone functions");
+                if parent.is_none() {
+                    fn_errors.spanned(
+                        arg,
+                        "fancyargs!() macro may only be used on methods inside an impl block",
+                    );
+                }
             }
             parse::FnArg::Captured(ref cap) => {
-                if cap.is_vararg() && index != last_index {
-                    panic!(
-                        "Invalid vararg argument {}: varargs must be the last argument",
-                        cap.name().unwrap_or(format!("UNKNOWN"))
+                let followed_by_kwargs = index + 1 == last_index && kwargs_last;
+                if cap.is_vararg() && index != last_index && !followed_by_kwargs {
+                    fn_errors.spanned(
+                        &cap.pat,
+                        "Invalid vararg argument: varargs must be the last argument, or only be followed by kwargs",
+                    );
+                }
+                if cap.is_kwargs() && index != last_index {
+                    fn_errors.spanned(
+                        &cap.pat,
+                        "Invalid kwargs argument: kwargs must be the last argument",
                     );
                 }
-                quote!( #arg )
+                cap.validate(&mut fn_errors);
             }
-            _ => {
-                panic!("Unsupported function argument");
+            parse::FnArg::Inherit(_) => {
+                fn_errors.spanned(arg, "Invalid argument: `..inherit()` is only supported by fancyargs!()");
             }
-        })
-        .collect::<Vec<_>>();
+        }
+    }
 
-    let is_pub = match ast.vis {
-        syn::Visibility::Public(_) => true,
-        _ => false,
-    };
+    let is_pub = matches!(ast.vis, syn::Visibility::Public(_));
+    let vis = ast.vis.clone();
+    let has_receiver = ast.decl.inputs.iter().any(parse::FnArg::is_receiver);
+    let captured = ast
+        .decl
+        .inputs
+        .iter()
+        .filter_map(|arg| arg.captured().cloned())
+        .collect::<Vec<_>>();
+    let receiver = ast
+        .decl
+        .inputs
+        .iter()
+        .find(|arg| arg.is_receiver())
+        .map(|arg| quote!( #arg ));
 
     let clean_definition = ast.into_upstream();
     let ident = &clean_definition.ident;
-
     let macro_export = if is_pub {
         quote!( #[macro_export] )
     } else {
         quote!()
     };
 
-    let output = quote!(
-        #clean_definition
+    let fn_path = match parent {
+        Some(parent) => {
+            let mut path = parent.self_path.clone();
+            path.segments.push(ident.clone().into());
+            path
+        }
+        None => ident.clone().into(),
+    };
+    let path = quote!( #fn_path );
+    // Function names like `new` or `connect` are common to many types, and
+    // exported macros share the namespace of the crate root, so the macros
+    // of methods and associated functions are prefixed with the type name
+    // (`client_new!`, `client_connect!`).
+    let macro_ident = match parent {
+        Some(parent) => {
+            let type_name = parent.self_path.segments.last().unwrap().value().ident.to_string();
+            syn::Ident::new(
+                &format!("{}_{}", snake_case(&type_name), ident.to_string().trim_start_matches("r#")),
+                ident.span(),
+            )
+        }
+        None => ident.clone(),
+    };
+    let empty_generics = syn::Generics::default();
+    let impl_generics = parent.map(|parent| parent.generics).unwrap_or(&empty_generics);
+    let generics = &clean_definition.decl.generics;
 
-        #macro_export
-        macro_rules! #ident {
-            ( $($input:tt)* ) => {
-                fancyargs::invoke!( #ident  (  #( #clean_args ),* ) ;  $($input)* )
+    // Defaults are computed after the earlier arguments are bound.
+    let names = expand::param_names(&captured);
+    for (index, def) in captured.iter().enumerate() {
+        for (referenced, ident) in expand::default_refs(def, &names) {
+            if referenced >= index {
+                fn_errors.spanned(
+                    &ident,
+                    format!(
+                        "Invalid default value: '{}' is not declared before this argument, defaults can only refer to earlier arguments",
+                        ident
+                    ),
+                );
             }
         }
-    );
-    output
+    }
+    // Aliases can not collide with the names or other aliases.
+    let mut keywords = captured
+        .iter()
+        .filter_map(parse::ArgCaptured::name)
+        .collect::<Vec<_>>();
+    for alias in captured.iter().flat_map(|arg| &arg.aliases) {
+        let name = alias.name.to_string();
+        if keywords.contains(&name) {
+            fn_errors.spanned(
+                &alias.name,
+                format!("Invalid alias: '{}' is already a keyword of this function", name),
+            );
+        } else {
+            keywords.push(name);
+        }
+    }
+    if let Some(arg) = captured.iter().filter(|arg| arg.cfgs().next().is_some()).nth(MAX_CFG_PARAMS) {
+        fn_errors.spanned(
+            &arg.pat,
+            format!(
+                "Invalid argument: at most {} parameters can have `#[cfg]` attributes, since a macro is generated for every combination of them",
+                MAX_CFG_PARAMS
+            ),
+        );
+    }
+    if options.declarative {
+        declarative::validate(&captured, &mut fn_errors);
+    }
+    if !fn_errors.is_empty() {
+        errors.extend(fn_errors);
+        return (clean_definition, quote!(), quote!());
+    }
+
+    let fn_docs = parse::doc_lines(&clean_definition.attrs);
+    let doc_rows = captured.iter().map(doc_row).collect::<Vec<_>>();
+
+    let generic_names = expand::Generics::new(vec![impl_generics, generics]);
+    let constness = clean_definition.constness;
+    let krate = options
+        .crate_path
+        .clone()
+        .unwrap_or_else(|| quote!( ::fancyargs ));
+    // `attrs` are the `#[cfg]`, docs and `#[macro_export]` attributes of the
+    // macro.
+    let build_macro = |captured: &[parse::ArgCaptured], attrs: TokenStream2| {
+        // The parameters keep their docs for the `..inherit()` directives of
+        // other functions.
+        let documented_args = captured
+            .iter()
+            .map(|arg| {
+                let docs = arg.attrs.iter().filter(|attr| attr.path.is_ident("doc"));
+                quote!( #( #docs )* #arg )
+            })
+            .collect::<Vec<_>>();
+        let clean_args = receiver
+            .iter()
+            .cloned()
+            .chain(documented_args.iter().cloned())
+            .collect::<Vec<_>>();
+        let impl_params = &impl_generics.params;
+        let params = &generics.params;
+        // `invoke!()` also continues `..inherit()` directives, so that the
+        // macro has a single arm in the docs.
+        let invoke_macro = quote!(
+            #attrs
+            macro_rules! #macro_ident {
+                ( $($input:tt)* ) => {
+                    #krate::invoke! { #constness #path <#impl_params> <#params> (  #( #clean_args ),* ) ;  $($input)* }
+                }
+            }
+        );
+        if !options.declarative {
+            return invoke_macro;
+        }
+        // Passes the parameters (without the receiver) to `inherit!()`.
+        let inherit_arm = quote!(
+            (@fancyargs_inherit $($tokens:tt)*) => {
+                #krate::inherit! { (#path) (#receiver) ( #( #documented_args ),* ) $($tokens)* }
+            };
+        );
+        let declarative_macro = declarative::build_macro(
+            &macro_ident,
+            &fn_path,
+            &clean_definition,
+            captured,
+            &generic_names,
+            inherit_arm,
+        );
+        // The declarative macro needs a second arm for `..inherit()`, which
+        // expands to an item. rustdoc shows every arm, so the docs show the
+        // macro that calls `invoke!()` instead.
+        quote!(
+            #[cfg(not(doc))]
+            #attrs
+            #declarative_macro
+
+            #[cfg(doc)]
+            #invoke_macro
+        )
+    };
+
+    // Arguments with `#[cfg]` attributes only exist in some configurations,
+    // so there is a macro for every combination of them.
+    let conditional = captured
+        .iter()
+        .enumerate()
+        .filter(|(_, arg)| arg.cfgs().next().is_some())
+        .map(|(index, arg)| {
+            let predicates = arg.cfgs().map(|attr| {
+                let predicate = &attr.tts;
+                quote!( all #predicate )
+            });
+            (index, quote!( all( #( #predicates ),* ) ))
+        })
+        .collect::<Vec<_>>();
+    let build_wrapper = |captured: &[parse::ArgCaptured]| {
+        build_wrapper(&clean_definition, parent.is_some(), captured, &vis)
+    };
+    let mut wrappers = Vec::new();
+    let macro_def = if conditional.is_empty() {
+        wrappers.extend(build_wrapper(&captured));
+        let docs = macro_docs(&fn_docs, has_receiver, doc_rows.iter());
+        build_macro(&captured, quote!( #docs #macro_export ))
+    } else {
+        let macro_defs = (0..1usize << conditional.len()).map(|enabled| {
+            let is_enabled = |bit: usize| enabled & (1 << bit) != 0;
+            let conditions = &conditional
+                .iter()
+                .enumerate()
+                .map(|(bit, (_, predicate))| {
+                    if is_enabled(bit) {
+                        quote!( #predicate )
+                    } else {
+                        quote!( not(#predicate) )
+                    }
+                })
+                .collect::<Vec<_>>();
+            let is_active = |index: &usize| match conditional.iter().position(|(other, _)| other == index) {
+                Some(bit) => is_enabled(bit),
+                None => true,
+            };
+            let active = captured
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| is_active(index))
+                .map(|(_, arg)| arg.clone())
+                .collect::<Vec<_>>();
+            if let Some(wrapper) = build_wrapper(&active) {
+                wrappers.push(quote!(
+                    #[cfg(all( #( #conditions ),* ))]
+                    #wrapper
+                ));
+            }
+            let rows = doc_rows
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| is_active(index))
+                .map(|(_, row)| row);
+            let docs = macro_docs(&fn_docs, has_receiver, rows);
+            build_macro(
+                &active,
+                quote!(
+                    #[cfg(all( #( #conditions ),* ))]
+                    #docs
+                    #macro_export
+                ),
+            )
+        });
+        let macro_defs = macro_defs.collect::<Vec<_>>();
+        quote!( #( #macro_defs )* )
+    };
+    let wrapper_fns = match parent {
+        _ if wrappers.is_empty() => quote!(),
+        Some(_) => quote!( #( #wrappers )* ),
+        None => {
+            // The hidden struct takes the name of the function in the type
+            // namespace. It is glob-imported, so that items of the same name
+            // shadow it instead of colliding with it. A shadowed struct is
+            // reported here by the name of the missing constant, instead of
+            // at every call site.
+            let holder_mod = expand::wrapper_ident(ident);
+            let check = syn::Ident::new("__fancyargs_name_is_taken_by_a_module_or_type", ident.span());
+            quote!(
+                #[doc(hidden)]
+                #[allow(non_camel_case_types)]
+                mod #holder_mod {
+                    pub struct #ident {}
+                }
+
+                #[doc(hidden)]
+                #[allow(unused_imports)]
+                #vis use #holder_mod::*;
+
+                #[allow(non_upper_case_globals)]
+                impl #holder_mod::#ident {
+                    #[doc(hidden)]
+                    pub const #check: () = ();
+
+                    #( #wrappers )*
+                }
+
+                const _: () = #ident::#check;
+            )
+        }
+    };
+    (clean_definition, wrapper_fns, macro_def)
+}
+
+/// Converts a type name to snake case, `HttpClient` becomes `http_client`.
+fn snake_case(name: &str) -> String {
+    let chars = name.chars().collect::<Vec<_>>();
+    let mut snake = String::new();
+    for (index, ch) in chars.iter().enumerate() {
+        if ch.is_uppercase() && index > 0 {
+            let prev = chars[index - 1];
+            let next_lower = chars.get(index + 1).filter(|next| next.is_lowercase()).is_some();
+            if prev.is_lowercase() || prev.is_numeric() || (prev.is_uppercase() && next_lower) {
+                snake.push('_');
+            }
+        }
+        snake.extend(ch.to_lowercase());
+    }
+    snake
+}
+
+/// A row of the argument table in the macro docs.
+fn doc_row(arg: &parse::ArgCaptured) -> String {
+    // Table cells can not contain a `|`, not even in code.
+    let code = |text: String| format!("`{}`", text.replace('|', "\\|"));
+    let name = match arg.name() {
+        Some(name) => format!("`{}`", name),
+        None => {
+            let pat = arg.binding_pat();
+            code(quote!( #pat ).to_string())
+        }
+    };
+    let ty = expand::display_ty(&arg.ty);
+    let mut kind = if arg.is_kwargs() {
+        "kwargs"
+    } else if arg.is_vararg() {
+        "vararg"
+    } else if arg.is_optional() {
+        "optional"
+    } else if arg.default.is_some() {
+        "default"
+    } else {
+        "required"
+    }
+    .to_string();
+    if arg.is_into() {
+        kind.push_str(", converted with `Into`");
+    }
+    if arg.is_kw_only() && !arg.is_kwargs() {
+        kind.push_str(", keyword-only");
+    } else if arg.is_pos_only() || arg.name().is_none() {
+        kind.push_str(", positional-only");
+    }
+    let default = match arg.default.as_ref() {
+        Some(default) if default.default_token.is_some() => "`Default::default()`".to_string(),
+        Some(default) => code(expand::display_expr(&default.value)),
+        None if arg.is_optional_type() => "`Default::default()`".to_string(),
+        None if arg.is_optional() => "`None`".to_string(),
+        None => String::new(),
+    };
+    let docs = arg.docs().iter().map(|line| line.trim()).collect::<Vec<_>>().join(" ");
+    format!(
+        "| {} | `{}` | {} | {} | {} |",
+        name,
+        ty,
+        kind,
+        default,
+        docs.replace('|', "\\|")
+    )
+}
+
+/// Docs of the generated macro: the docs of the function, followed by a
+/// table of the arguments.
+fn macro_docs<'a, I>(fn_docs: &[String], has_receiver: bool, rows: I) -> TokenStream2
+where
+    I: Iterator<Item = &'a String>,
+{
+    let rows = rows.collect::<Vec<_>>();
+    let mut lines = fn_docs.to_vec();
+    if rows.is_empty() && !has_receiver {
+        return quote!( #( #[doc = #lines] )* );
+    }
+    if !lines.is_empty() {
+        lines.push(String::new());
+    }
+    lines.push("# Arguments".to_string());
+    lines.push(String::new());
+    if has_receiver {
+        lines.push("The receiver is passed as first positional argument.".to_string());
+        lines.push(String::new());
+    }
+    lines.push("| Name | Type | Kind | Default | Description |".to_string());
+    lines.push("|------|------|------|---------|-------------|".to_string());
+    lines.extend(rows.into_iter().cloned());
+    let lines = lines.iter().map(|line| format!(" {}", line).trim_end().to_string());
+    quote!( #( #[doc = #lines] )* )
+}
+
+/// Builds the hidden wrapper of a function, see `expand::wrapper_ident()`.
+///
+/// The wrapper has the signature of the function, except that the
+/// parameters with hoisted defaults are `Option`s, which are filled in
+/// declaration order before the function is called. `associated` is set for
+/// functions in an impl block, `args` are their parameters without the
+/// receiver. Returns `None` if no default is hoisted.
+fn build_wrapper(
+    definition: &syn::ItemFn,
+    associated: bool,
+    args: &[parse::ArgCaptured],
+    vis: &syn::Visibility,
+) -> Option<TokenStream2> {
+    let hoisted = expand::hoisted(args, definition.constness.is_some());
+    if !hoisted.contains(&true) {
+        return None;
+    }
+    let names = expand::param_names(args);
+    let bindings = &(0..args.len())
+        .map(|index| expand::binding(&names, index))
+        .collect::<Vec<_>>();
+    let self_token = definition.decl.inputs.iter().find_map(|arg| match arg {
+        syn::FnArg::SelfRef(arg) => Some(arg.self_token),
+        syn::FnArg::SelfValue(arg) => Some(arg.self_token),
+        _ => None,
+    });
+    // A `mut self` receiver is not modified by the wrapper.
+    let receiver = definition.decl.inputs.iter().find_map(|arg| match arg {
+        syn::FnArg::SelfRef(arg) => Some(quote!( #arg )),
+        syn::FnArg::SelfValue(arg) => {
+            let self_token = &arg.self_token;
+            Some(quote!( #self_token ))
+        }
+        _ => None,
+    });
+    let params = receiver.into_iter().chain(args.iter().zip(bindings).zip(&hoisted).map(
+        |((arg, binding), hoisted)| {
+            let ty = &arg.ty;
+            if *hoisted {
+                quote!( #binding: ::core::option::Option<#ty> )
+            } else {
+                quote!( #binding: #ty )
+            }
+        },
+    ));
+    let defaults = args
+        .iter()
+        .zip(bindings)
+        .zip(&hoisted)
+        .filter(|(_, hoisted)| **hoisted)
+        .map(|((arg, binding), _)| {
+            let default = arg.default();
+            quote!(
+                let #binding = match #binding {
+                    ::core::option::Option::Some(#binding) => #binding,
+                    ::core::option::Option::None => #default,
+                };
+            )
+        });
+
+    let ident = &definition.ident;
+    let generics = &definition.decl.generics;
+    let generic_args = generics
+        .params
+        .iter()
+        .filter_map(|param| match param {
+            syn::GenericParam::Type(param) => Some(&param.ident),
+            syn::GenericParam::Const(param) => Some(&param.ident),
+            syn::GenericParam::Lifetime(_) => None,
+        })
+        .collect::<Vec<_>>();
+    let turbofish = if generic_args.is_empty() {
+        quote!()
+    } else {
+        quote!( ::< #( #generic_args ),* > )
+    };
+    let callee = if associated {
+        quote!( Self::#ident )
+    } else {
+        quote!( #ident )
+    };
+    let self_arg = self_token.map(|token| quote!( #token, ));
+    let mut call = quote!( #callee #turbofish ( #self_arg #( #bindings ),* ) );
+    if definition.unsafety.is_some() {
+        call = quote!( unsafe { #call } );
+    }
+    if definition.asyncness.is_some() {
+        call = quote!( #call.await );
+    }
+
+    // The wrapper of a free function is reached through the hidden struct,
+    // whose visibility is that of the function.
+    let vis = if associated {
+        vis.clone()
+    } else {
+        syn::parse_quote!(pub)
+    };
+    let asyncness = &definition.asyncness;
+    let unsafety = &definition.unsafety;
+    let wrapper = expand::wrapper_ident(ident);
+    let generic_params = &generics.params;
+    let where_clause = &generics.where_clause;
+    let output = &definition.decl.output;
+    Some(quote!(
+        #[doc(hidden)]
+        #[allow(dead_code, unused_unsafe)]
+        #vis #asyncness #unsafety fn #wrapper <#generic_params> ( #( #params ),* ) #output #where_clause {
+            #( #defaults )*
+            #call
+        }
+    ))
+}
+
+/// Builds the macros for all functions in an inherent impl block.
+///
+/// Methods are called on the first positional macro argument, associated
+/// functions through the path of the implemented type.
+/// The macros are emitted after the impl block, since macro definitions are
+/// not allowed inside of it.
+fn build_impl(
+    mut ast: parse::ItemImpl,
+    options: &parse::Options,
+    errors: &mut Errors,
+) -> TokenStream2 {
+    let options = match options.extract(&mut ast.attrs) {
+        Ok(options) => options,
+        Err(e) => {
+            errors.push(e);
+            parse::Options::default()
+        }
+    };
+    let self_path = match ast.self_path() {
+        Some(path) => path,
+        None => {
+            errors.spanned(
+                &ast.self_ty,
+                "fancyargs!() can only be used on impl blocks for named types",
+            );
+            syn::Path::from(syn::Ident::new("Self", Span::call_site()))
+        }
+    };
+
+    let parent = ImplContext {
+        self_path: &self_path,
+        generics: &ast.generics,
+    };
+    let mut clean_fns = Vec::new();
+    let mut default_fns = Vec::new();
+    let mut macros = Vec::new();
+    for item in ast.items {
+        let (clean_fn, default_fn, macro_def) = build_fn(item, Some(&parent), &options, errors);
+        clean_fns.push(clean_fn);
+        default_fns.push(default_fn);
+        macros.push(macro_def);
+    }
+
+    let attrs = &ast.attrs;
+    let unsafety = &ast.unsafety;
+    let impl_token = &ast.impl_token;
+    let self_ty = &ast.self_ty;
+    let (impl_generics, _, where_clause) = ast.generics.split_for_impl();
+
+    quote!(
+        #( #attrs )*
+        #unsafety #impl_token #impl_generics #self_ty #where_clause {
+            #( #clean_fns )*
+            #( #default_fns )*
+        }
+
+        #( #macros )*
+    )
+}
+
+fn build_item(item: parse::Item, options: &parse::Options, errors: &mut Errors) -> TokenStream2 {
+    match item {
+        parse::Item::Fn(item) => {
+            let (clean_definition, default_fns, macro_def) = build_fn(item, None, options, errors);
+            quote!(
+                #clean_definition
+                #default_fns
+
+                #macro_def
+            )
+        }
+        parse::Item::Impl(item) => build_impl(item, options, errors),
+    }
+}
+
+/// The `forward!()` macro of a function with `..inherit()` directives, with
+/// an arm for every inherited function.
+///
+/// `forward!(path)` calls the function with the inherited parameters,
+/// methods are called on the receiver with `forward!(path, receiver)`.
+fn forward_macro(inherits: &[(&parse::Inherit, &parse::Inherited)]) -> syn::Stmt {
+    let arms = inherits.iter().map(|(inherit, inherited)| {
+        let span = inherit.ident.span();
+        let path = &inherit.path;
+        let fn_path = expand::respan(inherited.fn_path.clone(), span);
+        let args = inherit.resolved.iter().flatten();
+        let call = match args
+            .map(|arg| match arg.pat {
+                syn::Pat::Ident(syn::PatIdent {
+                    ref ident,
+                    subpat: None,
+                    ..
+                }) => Ok(ident),
+                ref pat => Err(pat),
+            })
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(names) if inherited.receiver.is_empty() => quote!( #fn_path( #( #names ),* ) ),
+            Ok(names) => quote!( #fn_path( $recv, #( #names ),* ) ),
+            Err(pat) => {
+                let message = format!(
+                    "Invalid forward: parameter `{}` is not bound to a name, only named parameters can be forwarded",
+                    quote!( #pat )
+                );
+                quote!( ::core::compile_error!(#message) )
+            }
+        };
+        if inherited.receiver.is_empty() {
+            quote!( (#path) => { #call }; )
+        } else {
+            quote!( (#path, $recv:expr) => { #call }; )
+        }
+    });
+    let forward = syn::Ident::new("forward", inherits[0].0.ident.span());
+    syn::parse_quote!(
+        #[allow(unused_macros)]
+        macro_rules! #forward {
+            #( #arms )*
+        }
+    )
+}
+
+/// Builds an item once the parameters of all its `..inherit()` directives
+/// are known.
+///
+/// The parameters are requested one directive at a time: the item is passed
+/// to the macro of the inherited function, which passes it on to
+/// `inherit!()` along with its parameters, and those of the earlier
+/// directives.
+fn build_inheriting(
+    mut item: parse::Item,
+    inherited: Vec<parse::Inherited>,
+    options: &parse::Options,
+    errors: &mut Errors,
+) -> TokenStream2 {
+    let next = item
+        .inherits_mut()
+        .get(inherited.len())
+        .map(|inherit| inherit.path.clone());
+    if let Some(path) = next {
+        return quote!( #path!(@fancyargs_inherit [ #( (#inherited) )* ] #item); );
+    }
+    if inherited.is_empty() {
+        return build_item(item, options, errors);
+    }
+
+    let fns = match item {
+        parse::Item::Fn(ref mut item) => vec![item],
+        parse::Item::Impl(ref mut item) => item.items.iter_mut().collect(),
+    };
+    let mut inherited = inherited.iter();
+    for item in fns {
+        let mut inherits = Vec::new();
+        for inherit in item.inherits_mut() {
+            let inherited = inherited.next().expect("a parameter list for every directive");
+            match inherit.resolve(inherited) {
+                Ok(()) => inherits.push((&*inherit, inherited)),
+                Err(e) => errors.push(e),
+            }
+        }
+        if !inherits.is_empty() {
+            let forward = forward_macro(&inherits);
+            item.block.stmts.insert(0, forward);
+        }
+    }
+    // The item is parsed again, so that the keyword-only and
+    // positional-only markers of the imported parameters apply to the
+    // parameters around them.
+    match syn::parse2(quote!( #item )) {
+        Ok(item) => build_item(item, options, errors),
+        Err(e) => {
+            errors.push(e);
+            quote!()
+        }
+    }
 }
 
-// pub fn fancyargs(_attrs: TokenStream, input: TokenStream) -> TokenStream {
 #[proc_macro]
 pub fn fancyargs(input: TokenStream) -> TokenStream {
     let body: parse::MacroBody = match syn::parse(input) {
         Ok(body) => body,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let options = parse::Options::default();
+    let mut errors = Errors::default();
+    let items = body
+        .items
+        .into_iter()
+        .map(|item| build_inheriting(item, Vec::new(), &options, &mut errors))
+        .collect::<Vec<_>>();
+    let errors = errors.to_compile_errors();
+    quote!( #( #items )* #errors ).into()
+}
+
+/// Attribute version of [fancyargs!](macro.fancyargs.html) that only
+/// requires valid Rust syntax.
+///
+/// It can not be named `fancyargs` too, because attribute and function-like
+/// macros share the macro namespace.
+///
+/// Can be applied to a function or an inherent impl block.
+/// Parameters are marked with attributes instead of the custom syntax:
+///
+/// * `#[default(expr)]` instead of `name: T = expr`
+/// * `#[default]` instead of `name: T = default`
+/// * `#[optional]` instead of `name?: Option<T>`
+/// * `#[optional_type]` for an optional parameter whose type is not spelled
+///   `Option<T>`, see the usage notes
+/// * `#[vararg]` instead of `name*: Vec<T>`
+/// * `#[kwargs]` instead of `name**: HashMap<&'static str, T>`
+/// * `#[into]` instead of `name~: T`
+/// * `#[kw_only]` instead of the `*,` separator, it makes the parameter and
+///   all following ones keyword-only
+/// * `#[pos_only]` instead of the `/` separator, it makes the parameter and
+///   all preceding ones positional-only
+///
+/// `#[alias(old_name)]`, `#[cfg(..)]` attributes and doc comments are allowed
+/// on parameters too.
+///
+/// ## Options
+///
+/// Options can be passed as arguments (`#[fancy(declarative)]`).
+/// Inside [fancyargs!](macro.fancyargs.html), the same options can be set by
+/// adding a `#[fancy(...)]` attribute to a function or impl block.
+///
+/// * `declarative`: generate a self-contained `macro_rules!` that resolves the
+///   arguments without calling a proc macro at the call site. `#[kwargs]`
+///   parameters are not supported.
+/// * `crate = "path"`: the path under which fancyargs is reachable from the
+///   call site, for crates that re-export it (`crate = "$crate::__fancyargs"`).
+///   Defaults to `::fancyargs`, which crates that call the macros of a library
+///   can only resolve if they depend on fancyargs themselves, see
+///   [Exporting from a library](index.html#exporting-from-a-library).
+#[proc_macro_attribute]
+pub fn fancy(attrs: TokenStream, input: TokenStream) -> TokenStream {
+    let mut options = parse::Options::default();
+    let mut errors = Errors::default();
+    if let Err(e) = syn::parse::Parser::parse(
+        |input: syn::parse::ParseStream| options.parse_args(input),
+        attrs,
+    ) {
+        errors.push(e);
+    }
+    let item = match syn::parse::<parse::Item>(input) {
+        Ok(item) => build_item(item, &options, &mut errors),
         Err(e) => {
-            panic!("Could not parse macro body: \n{}", e);
+            errors.push(e);
+            quote!()
         }
     };
-    let items = body.fns.into_iter().map(build_fn);
-    quote!( #( #items )*).into()
+    let errors = errors.to_compile_errors();
+    quote!( #item #errors ).into()
 }
 
+/// Finds the argument that can be passed with the keyword `name`, which is
+/// its name or one of its aliases. The alias is returned if it was used.
+///
+/// Positional-only arguments are ignored, their names are not part of the
+/// public API.
 fn arg_pos_by_name<'a>(
     args: &'a [parse::ArgCaptured],
     name: &str,
-) -> Option<(usize, &'a parse::ArgCaptured)> {
+) -> Option<(usize, &'a parse::ArgCaptured, Option<&'a parse::Alias>)> {
     args.iter()
         .enumerate()
-        .find(|(_index, arg)| arg.name().map(|argname| argname == name).unwrap_or(false))
-        .map(|(index, val)| (index, val))
+        .filter(|(_index, arg)| !arg.is_pos_only())
+        .find_map(|(index, arg)| {
+            if arg.name().map(|argname| argname == name).unwrap_or(false) {
+                return Some((index, arg, None));
+            }
+            arg.aliases
+                .iter()
+                .find(|alias| alias.name == name)
+                .map(|alias| (index, arg, Some(alias)))
+        })
+}
+
+/// Continues `fancyargs!()` for an item with `..inherit()` directives, after
+/// the macro of an inherited function passed back its parameters.
+#[doc(hidden)]
+#[proc_macro]
+pub fn inherit(input: TokenStream) -> TokenStream {
+    match syn::parse::<parse::InheritInput>(input) {
+        Ok(input) => continue_inheriting(input).into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn continue_inheriting(input: parse::InheritInput) -> TokenStream2 {
+    let options = parse::Options::default();
+    let mut errors = Errors::default();
+    let item = build_inheriting(input.item, input.inherited, &options, &mut errors);
+    let errors = errors.to_compile_errors();
+    quote!( #item #errors )
 }
 
 #[doc(hidden)]
 #[proc_macro]
 pub fn invoke(input: TokenStream) -> TokenStream {
     // Parse arguments.
-    let invokation = match syn::parse::<parse::InvokationInput>(input) {
-        Ok(ast) => ast,
-        Err(e) => panic!("Could not parse arguments: {}", e),
+    let invokation = match syn::parse::<parse::InvokeInput>(input) {
+        Ok(parse::InvokeInput::Call(ast)) => ast,
+        Ok(parse::InvokeInput::Inherit(input)) => return continue_inheriting(input).into(),
+        Err(e) => return e.to_compile_error().into(),
     };
     let arg_definitions = invokation.args_captured();
-    let have_vararg = arg_definitions
+    let vararg_index = arg_definitions.iter().position(parse::ArgCaptured::is_vararg);
+    let have_vararg = vararg_index.is_some();
+    let have_kwargs = arg_definitions.iter().any(parse::ArgCaptured::is_kwargs);
+    // Arguments after the first keyword-only argument can not be passed
+    // positionally.
+    let positional_len = arg_definitions
         .iter()
-        .last()
-        .map(|arg| arg.is_vararg())
-        .unwrap_or(false);
+        .take_while(|arg| !arg.is_kw_only() && !arg.is_vararg() && !arg.is_kwargs())
+        .count();
+
+    let generics = expand::Generics::new(vec![&invokation.impl_generics, &invokation.generics]);
+    // Hoisted defaults are computed by the wrapper of the function.
+    let hoisted = expand::hoisted(&arg_definitions, invokation.constness.is_some());
+    let wrapped = hoisted.contains(&true);
+    let mut errors = Errors::default();
 
     let mut args: Vec<Option<TokenStream2>> = Vec::new();
     for _ in &arg_definitions {
         args.push(None);
     }
-    let mut varargs: Vec<syn::Expr> = Vec::new();
+    // Vararg elements with their position in the macro call.
+    let mut varargs: Vec<(usize, syn::Expr)> = Vec::new();
+    // Unmatched keyword arguments, collected by the kwargs argument.
+    let mut kwargs: Vec<(syn::Ident, usize, syn::Expr)> = Vec::new();
+    // Expressions are bound to temporaries if they have to be evaluated in
+    // a different order than the parameters. A partial call evaluates all of
+    // them before the closure is created.
+    let partial = invokation.partial;
+    let mut temps = if partial {
+        expand::Temporaries::all()
+    } else {
+        expand::Temporaries::default()
+    };
+    // The missing required arguments of a partial call, which become the
+    // parameters of the closure.
+    let mut params = Vec::new();
 
     let mut reached_keyword_args = false;
 
-    for (index, arg) in invokation.args.into_iter().enumerate() {
-        let (arg_index, arg_decl) = match arg.name {
+    // Errors that don't belong to a specific argument point at the first
+    // argument, since the macro invocation itself is not visible here.
+    let call_span = invokation
+        .args
+        .first()
+        .map(|pair| {
+            let arg = pair.into_value();
+            match arg.name {
+                Some(ref name) => name.span(),
+                None => arg.expr.span(),
+            }
+        })
+        .unwrap_or_else(Span::call_site);
+
+    let has_receiver = invokation.has_receiver();
+    let receiver_borrow = invokation.receiver_borrow();
+    let fn_name = invokation
+        .target_fn_path
+        .segments
+        .last()
+        .map(|segment| segment.value().ident.to_string())
+        .unwrap_or_default();
+    let signature = expand::signature(&fn_name, has_receiver, &arg_definitions);
+    // Arguments that were passed positionally, for error messages.
+    let mut positional = Vec::new();
+    let context = |positional: &[usize]| {
+        format!(
+            "\n  signature: {}{}",
+            signature,
+            expand::passed_positionally(&arg_definitions, has_receiver, positional)
+        )
+    };
+    let mut invokation_args = invokation.args.into_iter();
+
+    // Methods are called on the first positional argument.
+    let receiver = if has_receiver {
+        let missing = "Missing receiver: the first argument of a method must be positional";
+        match invokation_args.next() {
+            Some(ref arg) if arg.name.is_some() => {
+                errors.spanned(&arg.name, missing);
+                None
+            }
+            Some(arg) => Some(temps.bind_receiver(&arg.expr, receiver_borrow.clone())),
+            None => {
+                errors.push(syn::Error::new(call_span, missing));
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    for (index, arg) in invokation_args.enumerate() {
+        // Type errors point at the keyword, or the expression itself.
+        let span = match arg.name {
+            Some(ref name) => name.span(),
+            None => arg.expr.span(),
+        };
+        // A flag is ambiguous where a variable of the same name could be
+        // passed positionally.
+        let takes_positional = if reached_keyword_args {
+            vararg_index.map(|index| args[index].is_none()).unwrap_or(false)
+        } else {
+            index < positional_len || have_vararg
+        };
+        if let (true, Some(name), syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Bool(value), .. })) =
+            (arg.flag && takes_positional, &arg.name, &arg.expr)
+        {
+            errors.spanned(name, expand::ambiguous_flag(&name.to_string(), value.value));
+            continue;
+        }
+        let (arg_index, arg_decl, alias) = match arg.name {
             Some(name) => {
                 match arg_pos_by_name(&arg_definitions, &name.to_string()) {
                     Some(x) => {
                         // Check if the keyword argument was already specified.
                         if args.get(x.0).map(|x| x.is_some()).unwrap_or(false) {
-                            panic!("Duplicate keyword argument '{}'", name);
+                            let message = if arg.flag {
+                                expand::duplicate_flag(&name.to_string())
+                            } else {
+                                format!("Duplicate keyword argument '{}'", name)
+                            };
+                            errors.spanned(&name, message);
+                            continue;
                         }
                         reached_keyword_args = true;
                         x
                     }
+                    None if have_kwargs => {
+                        if kwargs.iter().any(|(other, _, _)| *other == name) {
+                            errors.spanned(
+                                &name,
+                                format!("Duplicate keyword argument '{}'", name),
+                            );
+                        } else {
+                            reached_keyword_args = true;
+                            kwargs.push((name, index, arg.expr));
+                        }
+                        continue;
+                    }
                     None => {
-                        panic!("Unknown keyword argument '{}'", name);
+                        let pos_only = arg_definitions
+                            .iter()
+                            .any(|arg| arg.is_pos_only() && arg.name() == Some(name.to_string()));
+                        let message = if pos_only {
+                            format!(
+                                "Invalid keyword argument: '{}' is a positional-only argument",
+                                name
+                            )
+                        } else {
+                            let unknown = match expand::closest_keyword(&name.to_string(), &arg_definitions) {
+                                Some(keyword) => format!(
+                                    "Unknown keyword argument '{}', did you mean '{}'?",
+                                    name, keyword
+                                ),
+                                None => format!("Unknown keyword argument '{}'", name),
+                            };
+                            unknown + &context(&positional)
+                        };
+                        errors.spanned(&name, message);
+                        continue;
                     }
                 }
             }
             None => {
                 if reached_keyword_args {
-                    if have_vararg {
-                        varargs.push(arg.expr);
+                    if vararg_index.map(|index| args[index].is_none()).unwrap_or(false) {
+                        varargs.push((index, arg.expr));
                         continue;
-                    } else {
-                        panic!("Invalid argument number {}: positional arguments may not follow after keyword arguments", index);
-                    }
-                } else {
-                    if have_vararg && index >= arg_definitions.len() - 1 {
-                        varargs.push(arg.expr);
+                    } else if have_vararg {
+                        errors.spanned(
+                            &arg.expr,
+                            "Invalid positional argument: the vararg was already passed as keyword argument",
+                        );
                         continue;
-                    } else if index >= arg_definitions.len() {
-                        panic!("Invalid positional argument number {}:  function only takes {} arguments", index + 1, arg_definitions.len());
                     } else {
-                        (index, &arg_definitions[index])
+                        errors.spanned(
+                            &arg.expr,
+                            "Invalid positional argument: positional arguments may not follow after keyword arguments",
+                        );
+                        continue;
                     }
+                } else if have_vararg && index >= positional_len {
+                    varargs.push((index, arg.expr));
+                    continue;
+                } else if let Some(def) = arg_definitions
+                    .get(index)
+                    .filter(|def| index >= positional_len && def.is_kw_only())
+                {
+                    errors.spanned(
+                        &arg.expr,
+                        format!(
+                            "Invalid positional argument: '{}' is a keyword-only argument",
+                            def.name().unwrap_or_default()
+                        ),
+                    );
+                    continue;
+                } else if index >= positional_len {
+                    errors.spanned(
+                        &arg.expr,
+                        format!(
+                            "Invalid positional argument number {}: function only takes {} arguments{}",
+                            index + 1,
+                            positional_len,
+                            context(&positional)
+                        ),
+                    );
+                    continue;
+                } else {
+                    positional.push(index);
+                    (index, &arg_definitions[index], None)
                 }
             }
         };
 
-        let expr = if arg_decl.is_optional() {
-            let expr = arg.expr;
-            quote!( Some(#expr) )
-        } else {
-            let expr = arg.expr;
-            quote!( #expr )
-        };
-        args[arg_index] = Some(expr);
+        let expr = temps.bind_arg(index, arg_decl, &arg.expr);
+        let value = expand::supplied(arg_decl, &generics, expr, span);
+        args[arg_index] = Some(match alias {
+            Some(alias) => {
+                let mut keyword = alias.name.clone();
+                keyword.set_span(span);
+                expand::deprecated_alias(arg_decl, alias, value, quote!( #keyword ))
+            }
+            None => value,
+        });
     }
 
     let mut finished_args = Vec::new();
+    // Whether the argument was passed explicitly, or is a default value.
+    // Varargs and kwargs are always collected from explicit arguments.
+    let mut explicit = Vec::new();
 
-    let varargs = &varargs;
     for (index, def) in arg_definitions.iter().enumerate() {
+        explicit.push(def.is_vararg() || def.is_kwargs() || args[index].is_some());
         if let Some(Some(value)) = args.get(index) {
             // Already have an argument.
-            finished_args.push(value.clone());
+            if hoisted[index] {
+                finished_args.push(expand::hoisted_supplied(value.clone()));
+            } else {
+                finished_args.push(value.clone());
+            }
         } else if def.is_vararg() {
-            finished_args.push(quote!(vec![ #( #varargs ),* ]));
-        } else if def.is_optional() {
-            finished_args.push(quote!(None));
-        } else {
-            if let Some(default_expr) = def.default() {
-                finished_args.push(quote!( #default_expr ));
+            let element = |value: TokenStream2, expr: &syn::Expr| {
+                expand::vararg_element(def, &generics, value, expr.span())
+            };
+            let spread = varargs.iter().find(|(_, expr)| expand::spread(expr).is_some());
+            if let Some((_, spread)) = spread {
+                // Runs of elements are chained with the spread iterators.
+                let mut parts = Vec::new();
+                let mut elements = Vec::new();
+                for (index, expr) in &varargs {
+                    match expand::spread(expr) {
+                        Some(iter) => {
+                            if !elements.is_empty() {
+                                let run = elements.drain(..);
+                                parts.push(expand::vararg_elements_iter(quote!( #( #run ),* )));
+                            }
+                            let iter = temps.bind(*index, iter);
+                            parts.push(expand::vararg_spread_iter(def, &generics, iter));
+                        }
+                        None => elements.push(element(temps.bind(*index, expr), expr)),
+                    }
+                }
+                if !elements.is_empty() {
+                    parts.push(expand::vararg_elements_iter(quote!( #( #elements ),* )));
+                }
+                match expand::vararg_chain(def, parts) {
+                    Ok(value) => finished_args.push(value),
+                    Err(message) => errors.spanned(spread, message),
+                }
             } else {
-                panic!(
-                    "Missing required argument '{}'",
-                    def.name().unwrap_or("??".into())
-                );
+                let elements = varargs
+                    .iter()
+                    .map(|(index, expr)| element(temps.bind(*index, expr), expr))
+                    .collect::<Vec<_>>();
+                finished_args.push(expand::vararg(def, quote!( #( #elements ),* )));
+            }
+        } else if def.is_kwargs() {
+            let entries = kwargs.iter().map(|(name, index, expr)| {
+                let value = temps.bind(*index, expr);
+                expand::kwargs_entry(def, &generics, name, value, name.span())
+            });
+            let entries = entries.collect::<Vec<_>>();
+            finished_args.push(expand::kwargs(quote!( #( #entries ),* )));
+        } else {
+            match expand::omitted(def, hoisted[index]) {
+                Some(value) => finished_args.push(value),
+                None if partial => {
+                    let param = syn::Ident::new(&format!("__fancyargs_param{}", index), Span::call_site());
+                    finished_args.push(quote!( #param ));
+                    explicit[index] = true;
+                    params.push(match generics.closure_param_ty(&def.ty) {
+                        Some(ty) => quote!( #param: #ty ),
+                        None => quote!( #param ),
+                    });
+                }
+                None => errors.push(syn::Error::new(
+                    call_span,
+                    expand::missing(def, index + has_receiver as usize) + &context(&positional),
+                )),
             }
         }
     }
 
-    let path = &invokation.target_fn_path;
+    // Explicit generic arguments are passed with a turbofish, the other
+    // generic parameters are inferred.
+    let generic_args = &invokation.generic_args;
+    for (index, arg) in generic_args.iter().enumerate() {
+        if generic_args[..index].iter().any(|other| other.name == arg.name) {
+            errors.spanned(
+                &arg.name,
+                format!("Duplicate generic argument '{}'", arg.name),
+            );
+        }
+    }
+    let turbofish = if generic_args.is_empty() {
+        quote!()
+    } else {
+        let params = invokation.generics.params.iter().filter_map(|param| {
+            let ident = match param {
+                syn::GenericParam::Type(param) => &param.ident,
+                syn::GenericParam::Const(param) => &param.ident,
+                syn::GenericParam::Lifetime(_) => return None,
+            };
+            Some(match generic_args.iter().find(|arg| arg.name == *ident) {
+                Some(arg) => arg.value.clone(),
+                None => quote!( _ ),
+            })
+        });
+        quote!( ::< #( #params ),* > )
+    };
 
-    quote!(
-        #path ( #( #finished_args ),* )
-    )
-    .into()
+    if !errors.is_empty() {
+        let errors = errors.to_compile_errors();
+        return quote!({ #errors }).into();
+    }
+
+    let path = if wrapped {
+        expand::wrapper_path(&invokation.target_fn_path)
+    } else {
+        invokation.target_fn_path.clone()
+    };
+    let call = |args: &[TokenStream2]| match receiver {
+        Some(ref receiver) => {
+            let method = &path.segments.last().unwrap().value().ident;
+            quote!(
+                (#receiver).#method #turbofish ( #( #args ),* )
+            )
+        }
+        None => quote!(
+            #path #turbofish ( #( #args ),* )
+        ),
+    };
+
+    // If a default at the call site refers to earlier arguments, the
+    // explicit arguments are bound first, in declaration order, and the
+    // defaults are computed from these bindings.
+    let names = expand::param_names(&arg_definitions);
+    let dependent = arg_definitions
+        .iter()
+        .zip(&explicit)
+        .zip(&hoisted)
+        .any(|((def, explicit), hoisted)| !explicit && !hoisted && !expand::default_refs(def, &names).is_empty());
+    // A partial call returns a closure that takes the missing arguments,
+    // defaults are computed when it is called.
+    let closure = |call: TokenStream2| {
+        if partial {
+            quote!( move | #( #params ),* | #call )
+        } else {
+            call
+        }
+    };
+    if !dependent {
+        return temps.finish(closure(call(&finished_args))).into();
+    }
+    let bindings = (0..arg_definitions.len())
+        .map(|index| expand::binding(&names, index))
+        .collect::<Vec<_>>();
+    let (explicit_bindings, explicit_values): (Vec<_>, Vec<_>) = bindings
+        .iter()
+        .zip(&finished_args)
+        .zip(&explicit)
+        .filter(|(_, explicit)| **explicit)
+        .map(|(pair, _)| pair)
+        .unzip();
+    let (default_bindings, default_values): (Vec<_>, Vec<_>) = bindings
+        .iter()
+        .zip(&finished_args)
+        .zip(&explicit)
+        .filter(|(_, explicit)| !**explicit)
+        .map(|(pair, _)| pair)
+        .unzip();
+    let call = call(&bindings.iter().map(|ident| quote!( #ident )).collect::<Vec<_>>());
+    temps
+        .finish(closure(quote!(
+            match ( #( #explicit_values, )* ) {
+                ( #( #explicit_bindings, )* ) => {
+                    #( let #default_bindings = #default_values; )*
+                    #call
+                }
+            }
+        )))
+        .into()
 }
@@ -1,15 +1,19 @@
 // The code is in this module is mostly copied 1-1 from [syn](https://github.com/dtolnay/syn) crate.
 // Original license and copyright apply.
 
-use proc_macro2::{Punct, Spacing, TokenTree};
+use proc_macro2::{Punct, Spacing, Span, TokenTree};
+use quote::{quote, quote_spanned, ToTokens};
 use std::iter::FromIterator;
 use syn::{
     parenthesized,
-    parse::{self, Parse},
+    parse::{self, Parse, Parser},
     punctuated::{Pair, Punctuated},
+    spanned::Spanned,
     token, Token,
 };
 
+use crate::error::Errors;
+
 #[derive(Debug, Clone)]
 pub struct ArgDefault {
     pub eq: syn::token::Eq,
@@ -20,44 +24,131 @@ pub struct ArgDefault {
 impl quote::ToTokens for ArgDefault {
     fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
         self.eq.to_tokens(tokens);
-        self.default_token.to_tokens(tokens);
-        if let Some(tok) = self.default_token.as_ref() {
-            tok.to_tokens(tokens);
+        match self.default_token.as_ref() {
+            Some(tok) => tok.to_tokens(tokens),
+            None => self.value.to_tokens(tokens),
         }
-        self.value.to_tokens(tokens);
     }
 }
 
+/// An old keyword of an argument, added with `#[alias(old, deprecated = "note")]`.
+#[derive(Clone, Debug)]
+pub struct Alias {
+    pub name: syn::Ident,
+    pub note: Option<syn::LitStr>,
+}
+
 #[derive(Clone, Debug)]
 pub struct ArgCaptured {
     pub pat: syn::Pat,
 
     // Custom field.
     pub vararg_token: Option<Token![*]>,
+    // Custom field. The two stars of `name**: HashMap<&str, T>`.
+    pub kwargs_token: Option<(Token![*], Token![*])>,
+    // Custom field. Set by `~` or an `#[into]` attribute.
+    pub into_token: Option<Token![~]>,
     // Custom field.
     pub optional_token: Option<Token![?]>,
+    // Custom field. Set by an `#[optional_type]` attribute.
+    pub optional_type: Option<Span>,
 
     pub colon_token: syn::token::Colon,
     pub ty: syn::Type,
 
     // Custom field.
     pub default: Option<ArgDefault>,
+    // Custom field. Set by the `*,` separator or a `#[kw_only]` attribute.
+    pub kw_only: Option<Span>,
+    // Custom field. Set by the `/` separator or a `#[pos_only]` attribute.
+    pub pos_only: Option<Span>,
+    // Custom field. The `#[cfg(..)]` attributes and doc comments.
+    pub attrs: Vec<syn::Attribute>,
+    // Custom field. Set by `#[alias(..)]` attributes.
+    pub aliases: Vec<Alias>,
 }
 
 impl ArgCaptured {
+    /// The keyword of the argument.
+    ///
+    /// Patterns only have a keyword if they are named with `name @ pattern`,
+    /// otherwise they can only be passed positionally.
     pub fn name(&self) -> Option<String> {
+        self.name_ident().map(ToString::to_string)
+    }
+
+    /// The keyword as identifier, which keeps the `r#` of raw identifiers.
+    pub fn name_ident(&self) -> Option<&syn::Ident> {
         match self.pat {
-            syn::Pat::Ident(ref ident) => Some(ident.ident.to_string()),
+            syn::Pat::Ident(ref ident) => Some(&ident.ident),
             _ => None,
         }
     }
 
+    /// The pattern that is bound in the function body.
+    ///
+    /// The name of `name @ pattern` is only used as keyword, so just the
+    /// pattern is bound.
+    pub fn binding_pat(&self) -> &syn::Pat {
+        match self.pat {
+            syn::Pat::Ident(syn::PatIdent {
+                subpat: Some((_, ref subpat)),
+                ..
+            }) => subpat,
+            ref pat => pat,
+        }
+    }
+
     pub fn is_vararg(&self) -> bool {
         self.vararg_token.is_some()
     }
 
+    pub fn is_kwargs(&self) -> bool {
+        self.kwargs_token.is_some()
+    }
+
     pub fn is_optional(&self) -> bool {
-        self.optional_token.is_some()
+        self.optional_token.is_some() || self.optional_type.is_some()
+    }
+
+    /// Whether supplied values (each element for varargs) are converted
+    /// with `Into::into`.
+    pub fn is_into(&self) -> bool {
+        self.into_token.is_some()
+    }
+
+    /// Whether the argument is optional with a type other than `Option`,
+    /// which is filled with `Default::default()` if omitted and converted
+    /// with `From::from` if supplied.
+    pub fn is_optional_type(&self) -> bool {
+        self.optional_type.is_some()
+    }
+
+    /// Whether the argument can be set with the flag shorthand, a bare
+    /// `name` for `name = true` and `!name` for `name = false`.
+    ///
+    /// Only `bool` arguments with a default value that can be passed as
+    /// keyword argument are flags.
+    pub fn is_flag(&self) -> bool {
+        self.default.is_some() && self.name().is_some() && !self.is_pos_only() && is_bool_ty(&self.ty)
+    }
+
+    pub fn is_kw_only(&self) -> bool {
+        self.kw_only.is_some()
+    }
+
+    pub fn is_pos_only(&self) -> bool {
+        self.pos_only.is_some()
+    }
+
+    /// The lines of the doc comments.
+    pub fn docs(&self) -> Vec<String> {
+        doc_lines(&self.attrs)
+    }
+
+    /// The `#[cfg(..)]` attributes, which decide whether the argument exists.
+    pub fn cfgs(&self) -> impl Iterator<Item = &syn::Attribute> {
+        self.attrs.iter().filter(|attr| attr.path.is_ident("cfg"))
     }
 }
 
@@ -65,14 +156,120 @@ impl ArgCaptured {
     pub fn default(&self) -> Option<&syn::Expr> {
         self.default.as_ref().map(|def| &def.value)
     }
+
+    /// Checks that the argument markers are valid for the argument type
+    /// and can be combined.
+    pub fn validate(&self, errors: &mut Errors) {
+        if self.is_kwargs() && !is_kwargs_ty(&self.ty) {
+            errors.spanned(
+                &self.ty,
+                "Invalid kwargs argument: kwargs must have type HashMap<&str, _> or BTreeMap<&str, _>",
+            );
+        }
+        if self.is_kwargs()
+            && (self.is_vararg()
+                || self.is_into()
+                || self.is_optional()
+                || self.default.is_some()
+                || self.is_kw_only()
+                || self.is_pos_only())
+        {
+            errors.spanned(
+                &self.pat,
+                "Invalid kwargs argument: kwargs can not be combined with other argument markers",
+            );
+        }
+        if self.is_optional() && !self.is_optional_type() && !is_option_ty(&self.ty) {
+            errors.spanned(
+                &self.ty,
+                "Invalid optional argument: must have type Option<_>, add #[optional_type] for other types",
+            );
+        }
+        let optional = match (self.optional_token.as_ref(), self.optional_type) {
+            (Some(tok), _) => Some(tok.span),
+            (None, span) => span,
+        };
+        if let Some(span) = optional {
+            if self.is_vararg() || self.default.is_some() {
+                errors.push(syn::Error::new(
+                    span,
+                    "Invalid optional argument: optional arguments may not have a default value or be a vararg",
+                ));
+            }
+        }
+        if let (Some(tok), true) = (self.into_token.as_ref(), self.is_optional_type()) {
+            errors.push(syn::Error::new(
+                tok.span,
+                "Invalid into argument: #[optional_type] arguments are already converted with From::from",
+            ));
+        }
+        if let Some(default) = self.default.as_ref() {
+            if self.is_vararg() {
+                errors.spanned(
+                    default,
+                    "Invalid vararg argument: vararg arguments may not have a default value",
+                );
+            }
+        }
+        if let (Some(span), true) = (self.pos_only, self.is_kw_only()) {
+            errors.push(syn::Error::new(
+                span,
+                "Invalid argument: an argument can not be both positional-only and keyword-only",
+            ));
+        }
+        if let Some(alias) = self.aliases.first() {
+            if self.name().is_none() || self.is_pos_only() || self.is_kwargs() {
+                errors.spanned(
+                    &alias.name,
+                    "Invalid alias: only arguments that can be passed as keyword argument can have aliases",
+                );
+            }
+        }
+        if self.is_kw_only() && !self.is_kwargs() {
+            if self.is_vararg() {
+                errors.spanned(
+                    &self.pat,
+                    "Invalid vararg argument: varargs can not be keyword-only",
+                );
+            } else if self.name().is_none() {
+                errors.spanned(
+                    &self.pat,
+                    "Invalid keyword-only argument: keyword-only arguments must be named",
+                );
+            }
+        }
+    }
 }
 
 impl quote::ToTokens for ArgCaptured {
     fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        if let Some(span) = self.kw_only {
+            tokens.extend(quote_spanned!(span=> #[kw_only]));
+        }
+        if let Some(span) = self.pos_only {
+            tokens.extend(quote_spanned!(span=> #[pos_only]));
+        }
+        if let Some(span) = self.optional_type {
+            tokens.extend(quote_spanned!(span=> #[optional_type]));
+        }
+        for alias in &self.aliases {
+            let name = &alias.name;
+            tokens.extend(match alias.note {
+                Some(ref note) => quote!( #[alias(#name, deprecated = #note)] ),
+                None => quote!( #[alias(#name)] ),
+            });
+        }
         self.pat.to_tokens(tokens);
         if let Some(tok) = self.vararg_token.as_ref() {
             tok.to_tokens(tokens);
         }
+        if let Some((first, second)) = self.kwargs_token.as_ref() {
+            first.to_tokens(tokens);
+            second.to_tokens(tokens);
+        }
+        if let Some(tok) = self.into_token.as_ref() {
+            tok.to_tokens(tokens);
+        }
         if let Some(tok) = self.optional_token.as_ref() {
             tok.to_tokens(tokens);
         }
@@ -85,14 +282,195 @@ impl quote::ToTokens for ArgCaptured {
     }
 }
 
+/// The `..inherit(path)` directive, which imports the parameters of another
+/// fancy function.
+///
+/// The parameters are requested from the macro of `path` and spliced in
+/// place of the directive, see `Inherited`.
+#[derive(Debug)]
+pub struct Inherit {
+    pub dot2_token: Token![..],
+    pub ident: syn::Ident,
+    pub paren_token: token::Paren,
+    pub path: syn::Path,
+    // Set by the `*,` separator or a `#[kw_only]` attribute.
+    pub kw_only: Option<Span>,
+    // Set by the `/` separator or a `#[pos_only]` attribute.
+    pub pos_only: Option<Span>,
+    // The imported parameters, once they are known.
+    pub resolved: Option<Vec<ArgCaptured>>,
+}
+
+impl Inherit {
+    /// Splices in the parameters passed back by the macro of `self.path`.
+    ///
+    /// The parameters are respanned to the directive, so that the function
+    /// body can refer to them.
+    pub fn resolve(&mut self, inherited: &Inherited) -> parse::Result<()> {
+        let span = self.ident.span();
+        let args = crate::expand::respan(inherited.args.clone(), span);
+        let mut args = fn_inputs
+            .parse2(args)?
+            .into_iter()
+            .filter_map(|arg| match arg {
+                FnArg::Captured(cap) => Some(cap),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        for arg in args.iter_mut() {
+            if self.kw_only.is_some() && !arg.is_vararg() && !arg.is_kwargs() && !arg.is_kw_only() {
+                arg.kw_only = self.kw_only;
+            }
+            if self.pos_only.is_some() && !arg.is_pos_only() {
+                arg.pos_only = self.pos_only;
+            }
+        }
+        self.resolved = Some(args);
+        Ok(())
+    }
+}
+
+impl quote::ToTokens for Inherit {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        match self.resolved {
+            Some(ref args) => {
+                let args = args.iter().map(|arg| {
+                    let attrs = &arg.attrs;
+                    quote!( #( #attrs )* #arg )
+                });
+                tokens.extend(quote!( #( #args ),* ));
+            }
+            None => {
+                if let Some(span) = self.kw_only {
+                    tokens.extend(quote_spanned!(span=> #[kw_only]));
+                }
+                if let Some(span) = self.pos_only {
+                    tokens.extend(quote_spanned!(span=> #[pos_only]));
+                }
+                self.dot2_token.to_tokens(tokens);
+                self.ident.to_tokens(tokens);
+                let path = &self.path;
+                self.paren_token.surround(tokens, |tokens| path.to_tokens(tokens));
+            }
+        }
+    }
+}
+
+fn inherit(input: parse::ParseStream) -> parse::Result<Inherit> {
+    let attrs = input.call(syn::Attribute::parse_outer)?;
+    let dot2_token = input.parse()?;
+    let ident: syn::Ident = input.parse()?;
+    if ident != "inherit" {
+        return Err(parse::Error::new(ident.span(), "expected `..inherit(path)`"));
+    }
+    let content;
+    let mut inherit = Inherit {
+        dot2_token,
+        ident,
+        paren_token: parenthesized!(content in input),
+        path: content.call(syn::Path::parse_mod_style)?,
+        kw_only: None,
+        pos_only: None,
+        resolved: None,
+    };
+    for attr in attrs {
+        let span = attr.pound_token.spans[0];
+        if attr.path.is_ident("kw_only") && attr.tts.is_empty() {
+            inherit.kw_only = Some(span);
+        } else if attr.path.is_ident("pos_only") && attr.tts.is_empty() {
+            inherit.pos_only = Some(span);
+        } else {
+            return Err(parse::Error::new_spanned(&attr, "unsupported attribute on `..inherit()`"));
+        }
+    }
+    Ok(inherit)
+}
+
+/// Whether the input starts with an `..inherit(path)` directive, possibly
+/// after attributes.
+fn peek_inherit(input: parse::ParseStream) -> bool {
+    let ahead = input.fork();
+    ahead.call(syn::Attribute::parse_outer).is_ok() && ahead.peek(Token![..]) && !ahead.peek(Token![...])
+}
+
+/// The parameters of a fancy function, as its macro passes them back for an
+/// `..inherit()` directive: `(<fn path>) (<receiver>) (<parameters>)`.
+#[derive(Clone, Debug)]
+pub struct Inherited {
+    pub fn_path: proc_macro2::TokenStream,
+    pub receiver: proc_macro2::TokenStream,
+    pub args: proc_macro2::TokenStream,
+}
+
+impl Parse for Inherited {
+    fn parse(input: parse::ParseStream) -> parse::Result<Self> {
+        let group = |input: parse::ParseStream| -> parse::Result<proc_macro2::TokenStream> {
+            let content;
+            parenthesized!(content in input);
+            content.parse()
+        };
+        Ok(Inherited {
+            fn_path: group(input)?,
+            receiver: group(input)?,
+            args: group(input)?,
+        })
+    }
+}
+
+impl quote::ToTokens for Inherited {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        let Inherited {
+            fn_path,
+            receiver,
+            args,
+        } = self;
+        tokens.extend(quote!( (#fn_path) (#receiver) (#args) ));
+    }
+}
+
+/// The input of the hidden `inherit!()` macro:
+/// `<inherited> [(<inherited>)..] <item>`.
+///
+/// The first parameter list was just passed back by a macro, the bracketed
+/// ones are those of the earlier directives of the item.
+pub struct InheritInput {
+    pub inherited: Vec<Inherited>,
+    pub item: Item,
+}
+
+impl Parse for InheritInput {
+    fn parse(input: parse::ParseStream) -> parse::Result<Self> {
+        let last = input.parse()?;
+        InheritInput::parse_rest(last, input)
+    }
+}
+
+impl InheritInput {
+    /// Parses the input after the parameter list that was passed back.
+    fn parse_rest(last: Inherited, input: parse::ParseStream) -> parse::Result<Self> {
+        let content;
+        syn::bracketed!(content in input);
+        let mut inherited = Vec::new();
+        while !content.is_empty() {
+            let group;
+            parenthesized!(group in content);
+            inherited.push(group.parse()?);
+        }
+        inherited.push(last);
+        Ok(InheritInput {
+            inherited,
+            item: input.parse()?,
+        })
+    }
+}
+
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum FnArg {
     SelfRef(syn::ArgSelfRef),
     SelfValue(syn::ArgSelf),
     Captured(ArgCaptured),
-    #[allow(dead_code)]
-    Inferred(syn::Pat),
-    Ignored(syn::Type),
+    Inherit(Inherit),
 }
 
 impl FnArg {
@@ -100,16 +478,33 @@ impl FnArg {
         match self {
             FnArg::SelfRef(x) => syn::FnArg::SelfRef(x),
             FnArg::SelfValue(x) => syn::FnArg::SelfValue(x),
-            FnArg::Captured(cap) => syn::FnArg::Captured(syn::ArgCaptured {
-                pat: cap.pat,
-                colon_token: cap.colon_token,
-                ty: cap.ty,
-            }),
-            FnArg::Inferred(x) => syn::FnArg::Inferred(x),
-            FnArg::Ignored(x) => syn::FnArg::Ignored(x),
+            FnArg::Captured(cap) => {
+                // syn has no attributes on arguments, so the `#[cfg]`
+                // attributes are emitted as part of the pattern. Doc comments
+                // are not allowed on arguments.
+                let pat = cap.binding_pat();
+                let pat = if cap.cfgs().next().is_some() {
+                    let cfgs = cap.cfgs();
+                    syn::Pat::Verbatim(syn::PatVerbatim {
+                        tts: quote!( #( #cfgs )* #pat ),
+                    })
+                } else {
+                    pat.clone()
+                };
+                syn::FnArg::Captured(syn::ArgCaptured {
+                    pat,
+                    colon_token: cap.colon_token,
+                    ty: cap.ty,
+                })
+            }
+            FnArg::Inherit(_) => unreachable!("`..inherit()` is resolved before the expansion"),
         }
     }
 
+    pub fn is_receiver(&self) -> bool {
+        matches!(self, FnArg::SelfRef(_) | FnArg::SelfValue(_))
+    }
+
     pub fn captured(&self) -> Option<&ArgCaptured> {
         match self {
             FnArg::Captured(ref cap) => Some(cap),
@@ -128,8 +523,7 @@ impl quote::ToTokens for FnArg {
             FnArg::SelfRef(item) => item.to_tokens(tokens),
             FnArg::SelfValue(item) => item.to_tokens(tokens),
             FnArg::Captured(item) => item.to_tokens(tokens),
-            FnArg::Inferred(item) => item.to_tokens(tokens),
-            FnArg::Ignored(item) => item.to_tokens(tokens),
+            FnArg::Inherit(item) => item.to_tokens(tokens),
         }
     }
 }
@@ -150,32 +544,196 @@ fn arg_self(input: parse::ParseStream) -> parse::Result<syn::ArgSelf> {
     })
 }
 
-fn is_vararg_ty(ty: &syn::Type) -> bool {
+/// The lines of the doc comments (`#[doc = "..."]` attributes), without the
+/// leading space of `/// text`.
+pub fn doc_lines(attrs: &[syn::Attribute]) -> Vec<String> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("doc"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(syn::Meta::NameValue(syn::MetaNameValue {
+                lit: syn::Lit::Str(text),
+                ..
+            })) => {
+                let text = text.value();
+                Some(text.strip_prefix(' ').unwrap_or(&text).to_string())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn is_kwargs_ty(ty: &syn::Type) -> bool {
     match ty {
-        // syn::Type::Reference(inner) => {
-        //     match *inner.elem {
-        //         syn::Type::Slice(_) => { true }
-        //         _ => false,
-        //     }
-        // },
-        syn::Type::Path(path) => path.path.segments[0].ident == "Vec",
+        syn::Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .map(|segment| {
+                let ident = &segment.value().ident;
+                ident == "HashMap" || ident == "BTreeMap"
+            })
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+fn is_bool_ty(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(syn::TypePath { qself: None, path }) => path
+            .segments
+            .last()
+            .map(|segment| segment.value().ident == "bool" && segment.value().arguments.is_empty())
+            .unwrap_or(false),
         _ => false,
     }
 }
 
 fn is_option_ty(ty: &syn::Type) -> bool {
     match ty {
-        syn::Type::Path(path) => path.path.segments[0].ident == "Option",
+        syn::Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .map(|segment| segment.value().ident == "Option")
+            .unwrap_or(false),
         _ => false,
     }
 }
 
+/// Parses the parenthesized expression of a `#[default(expr)]` attribute.
+fn attr_default_value(input: parse::ParseStream) -> parse::Result<syn::Expr> {
+    let content;
+    parenthesized!(content in input);
+    content.parse()
+}
+
+/// Parses the arguments of an `#[alias(old, other, deprecated = "note")]`
+/// attribute.
+fn attr_aliases(input: parse::ParseStream) -> parse::Result<Vec<Alias>> {
+    let content;
+    parenthesized!(content in input);
+    let mut names = Vec::new();
+    let mut note = None;
+    while !content.is_empty() {
+        let name: syn::Ident = content.parse()?;
+        if name == "deprecated" && content.peek(Token![=]) {
+            content.parse::<Token![=]>()?;
+            note = Some(content.parse::<syn::LitStr>()?);
+        } else {
+            names.push(name);
+        }
+        if content.is_empty() {
+            break;
+        }
+        content.parse::<Token![,]>()?;
+    }
+    if names.is_empty() {
+        return Err(content.error("expected the old keyword of the argument"));
+    }
+    Ok(names
+        .into_iter()
+        .map(|name| Alias {
+            name,
+            note: note.clone(),
+        })
+        .collect())
+}
+
+/// Applies the parameter attributes of the attribute syntax
+/// (`#[default(expr)]`, `#[default]`, `#[optional]`, `#[optional_type]`,
+/// `#[vararg]`, `#[kwargs]`, `#[into]`, `#[kw_only]` and `#[pos_only]`).
+///
+/// They are translated to the same tokens that the custom syntax
+/// (`= expr`, `= default`, `?`, `*`, `**`, `~` and the `*,` and `/` separators)
+/// produces, so both syntaxes result in identical arguments.
+/// `#[optional_type]` has no custom syntax, it implies `?`.
+/// `#[alias(..)]`, `#[cfg(..)]` attributes and doc comments are kept on the argument.
+fn apply_arg_attr(arg: &mut ArgCaptured, attr: syn::Attribute) -> parse::Result<()> {
+    let span = attr.pound_token.spans[0];
+
+    if attr.path.is_ident("cfg") || attr.path.is_ident("doc") {
+        arg.attrs.push(attr);
+    } else if attr.path.is_ident("default") {
+        if arg.default.is_some() {
+            return Err(parse::Error::new_spanned(&attr, "duplicate default value"));
+        }
+        let (default_token, value) = if attr.tts.is_empty() {
+            (
+                Some(Token![default](span)),
+                syn::parse_str("::core::default::Default::default()").unwrap(),
+            )
+        } else {
+            (None, attr_default_value.parse2(attr.tts)?)
+        };
+        arg.default = Some(ArgDefault {
+            eq: Token![=](span),
+            default_token,
+            value,
+        });
+    } else if attr.path.is_ident("optional") {
+        if arg.is_optional() {
+            return Err(parse::Error::new_spanned(&attr, "duplicate optional marker"));
+        }
+        arg.optional_token = Some(Token![?](span));
+    } else if attr.path.is_ident("optional_type") {
+        if arg.is_optional_type() {
+            return Err(parse::Error::new_spanned(&attr, "duplicate optional_type marker"));
+        }
+        arg.optional_type = Some(span);
+    } else if attr.path.is_ident("vararg") {
+        if arg.is_vararg() {
+            return Err(parse::Error::new_spanned(&attr, "duplicate vararg marker"));
+        }
+        arg.vararg_token = Some(Token![*](span));
+    } else if attr.path.is_ident("kwargs") {
+        if arg.is_kwargs() {
+            return Err(parse::Error::new_spanned(&attr, "duplicate kwargs marker"));
+        }
+        arg.kwargs_token = Some((Token![*](span), Token![*](span)));
+    } else if attr.path.is_ident("into") {
+        if arg.is_into() {
+            return Err(parse::Error::new_spanned(&attr, "duplicate into marker"));
+        }
+        arg.into_token = Some(Token![~](span));
+    } else if attr.path.is_ident("alias") {
+        arg.aliases.extend(attr_aliases.parse2(attr.tts)?);
+    } else if attr.path.is_ident("kw_only") {
+        if arg.is_kw_only() {
+            return Err(parse::Error::new_spanned(&attr, "duplicate kw_only marker"));
+        }
+        arg.kw_only = Some(span);
+    } else if attr.path.is_ident("pos_only") {
+        if arg.is_pos_only() {
+            return Err(parse::Error::new_spanned(&attr, "duplicate pos_only marker"));
+        }
+        arg.pos_only = Some(span);
+    } else {
+        return Err(parse::Error::new_spanned(
+            &attr,
+            "unsupported parameter attribute",
+        ));
+    }
+    Ok(())
+}
+
 fn arg_captured(input: parse::ParseStream) -> parse::Result<ArgCaptured> {
-    let arg = ArgCaptured {
-        pat: input.parse()?,
-        vararg_token: {
-            if input.peek(Token![*]) {
-                Some(input.parse::<Token![*]>().unwrap())
+    let attrs = input.call(syn::Attribute::parse_outer)?;
+    let pat = input.parse()?;
+    let (vararg_token, kwargs_token) = if input.peek(Token![*]) && input.peek2(Token![*]) {
+        (None, Some((input.parse()?, input.parse()?)))
+    } else if input.peek(Token![*]) {
+        (Some(input.parse::<Token![*]>().unwrap()), None)
+    } else {
+        (None, None)
+    };
+    let mut arg = ArgCaptured {
+        pat,
+        vararg_token,
+        kwargs_token,
+        into_token: {
+            if input.peek(Token![~]) {
+                Some(input.parse::<Token![~]>().unwrap())
             } else {
                 None
             }
@@ -213,7 +771,7 @@ fn arg_captured(input: parse::ParseStream) -> parse::Result<ArgCaptured> {
                 let (default_token, value) = if input.peek(syn::token::Default) {
                     (
                         Some(input.parse()?),
-                        syn::parse_str("Default::default()").unwrap(),
+                        syn::parse_str("::core::default::Default::default()").unwrap(),
                     )
                 } else {
                     (None, input.parse()?)
@@ -228,38 +786,14 @@ fn arg_captured(input: parse::ParseStream) -> parse::Result<ArgCaptured> {
                 None
             }
         },
+        optional_type: None,
+        kw_only: None,
+        pos_only: None,
+        attrs: Vec::new(),
+        aliases: Vec::new(),
     };
-    if arg.is_vararg() {
-        if !is_vararg_ty(&arg.ty) {
-            let name = arg.name().unwrap_or(format!("UNKNOWN"));
-            panic!(
-                "Invalid vararg argument {:?}: varargs must have type Vec<_>",
-                name
-            );
-        }
-    }
-    if arg.is_optional() {
-        if !is_option_ty(&arg.ty) {
-            let name = arg.name().unwrap_or(format!("UNKNOWN"));
-            panic!(
-                "Invalid optional argument {:?}: must have type Option<_>",
-                name
-            );
-        }
-    }
-    if arg.is_optional() && (arg.is_vararg() || arg.default.is_some()) {
-        let name = arg.name().unwrap_or(format!("UNKNOWN"));
-        panic!(
-            "Invalid argument {}: optional arguments may not have a default value or be a vararg",
-            name
-        );
-    }
-    if arg.is_vararg() && arg.default.is_some() {
-        let name = arg.name().unwrap_or(format!("UNKNOWN"));
-        panic!(
-            "Invalid argument {}: vararg arguments may not have a default value",
-            name
-        );
+    for attr in attrs {
+        apply_arg_attr(&mut arg, attr)?;
     }
     Ok(arg)
 }
@@ -280,19 +814,132 @@ impl parse::Parse for FnArg {
             }
         }
 
-        let ahead = input.fork();
-        let err = match ahead.call(arg_captured) {
-            Ok(_) => return input.call(arg_captured).map(FnArg::Captured),
-            Err(err) => err,
-        };
+        if let Some(pat) = peek_untyped(input) {
+            return Err(parse::Error::new_spanned(
+                pat,
+                "Invalid argument: parameters must have a type, like `name: Type`",
+            ));
+        }
+        input.call(arg_captured).map(FnArg::Captured)
+    }
+}
 
-        let ahead = input.fork();
-        if ahead.parse::<syn::Type>().is_ok() {
-            return input.parse().map(FnArg::Ignored);
+/// Whether the input starts with the keyword-only separator `*`, which is
+/// followed by a comma or the end of the parameter list.
+fn peek_kw_only_separator(input: parse::ParseStream) -> bool {
+    let ahead = input.fork();
+    ahead.parse::<Token![*]>().is_ok() && (ahead.is_empty() || ahead.peek(Token![,]))
+}
+
+/// Whether the input starts with the positional-only separator `/`, which is
+/// followed by a comma or the end of the parameter list.
+fn peek_pos_only_separator(input: parse::ParseStream) -> bool {
+    let ahead = input.fork();
+    ahead.parse::<Token![/]>().is_ok() && (ahead.is_empty() || ahead.peek(Token![,]))
+}
+
+/// The pattern of a parameter without a type, like `a` in `fn f(a, b: u8)`.
+fn peek_untyped(input: parse::ParseStream) -> Option<syn::Pat> {
+    let ahead = input.fork();
+    let _ = ahead.call(syn::Attribute::parse_outer);
+    let pat = ahead.parse().ok()?;
+    if ahead.is_empty() || ahead.peek(Token![,]) {
+        Some(pat)
+    } else {
+        None
+    }
+}
+
+/// Marks all parameters parsed so far as positional-only.
+fn mark_pos_only(inputs: &mut Punctuated<FnArg, Token![,]>, span: Span) {
+    for arg in inputs.iter_mut() {
+        match arg {
+            FnArg::Captured(ref mut cap) if !cap.is_pos_only() => cap.pos_only = Some(span),
+            FnArg::Inherit(ref mut inherit) if inherit.pos_only.is_none() => inherit.pos_only = Some(span),
+            _ => {}
         }
+    }
+}
 
-        Err(err)
+/// Parses the parameter list of a function.
+///
+/// Handles the keyword-only separator `*,` and marks all following
+/// parameters (except for varargs and kwargs) as keyword-only, as does a `#[kw_only]`
+/// attribute.
+/// Likewise the positional-only separator `/` and a `#[pos_only]` attribute
+/// mark all preceding parameters as positional-only.
+/// `..inherit(path)` directives are marked like parameters, and pass the
+/// markers on to the parameters they import.
+fn fn_inputs(input: parse::ParseStream) -> parse::Result<Punctuated<FnArg, Token![,]>> {
+    let mut inputs = Punctuated::new();
+    let mut kw_only = None;
+    let mut pos_only = false;
+    while !input.is_empty() {
+        if peek_pos_only_separator(input) {
+            let slash: Token![/] = input.parse()?;
+            if pos_only {
+                return Err(parse::Error::new(
+                    slash.spans[0],
+                    "duplicate positional-only separator",
+                ));
+            }
+            if kw_only.is_some() {
+                return Err(parse::Error::new(
+                    slash.spans[0],
+                    "the positional-only separator must come before the keyword-only separator",
+                ));
+            }
+            pos_only = true;
+            mark_pos_only(&mut inputs, slash.spans[0]);
+        } else if peek_kw_only_separator(input) {
+            let star: Token![*] = input.parse()?;
+            if kw_only.is_some() {
+                return Err(parse::Error::new(
+                    star.spans[0],
+                    "duplicate keyword-only separator",
+                ));
+            }
+            kw_only = Some(star.spans[0]);
+        } else {
+            let mut arg = if peek_inherit(input) {
+                input.call(inherit).map(FnArg::Inherit)?
+            } else {
+                input.parse()?
+            };
+            let mut marked_pos_only = None;
+            match arg {
+                FnArg::Captured(ref mut cap) => {
+                    if kw_only.is_none() {
+                        kw_only = cap.kw_only;
+                    } else if !cap.is_vararg() && !cap.is_kwargs() && !cap.is_kw_only() {
+                        cap.kw_only = kw_only;
+                    }
+                    marked_pos_only = cap.pos_only;
+                }
+                FnArg::Inherit(ref mut inherit) => {
+                    if kw_only.is_none() {
+                        kw_only = inherit.kw_only;
+                    } else if inherit.kw_only.is_none() {
+                        inherit.kw_only = kw_only;
+                    }
+                    marked_pos_only = inherit.pos_only;
+                }
+                _ => {}
+            }
+            if let Some(span) = marked_pos_only {
+                mark_pos_only(&mut inputs, span);
+            }
+            inputs.push_value(arg);
+        }
+        if input.is_empty() {
+            break;
+        }
+        let comma: Token![,] = input.parse()?;
+        if !inputs.empty_or_trailing() {
+            inputs.push_punct(comma);
+        }
     }
+    Ok(inputs)
 }
 
 #[derive(Debug)]
@@ -334,7 +981,11 @@ impl ItemFn {
                 generics: decl.generics,
                 paren_token: decl.paren_token,
                 inputs: {
-                    let pairs = decl.inputs.into_pairs().map(|pair| match pair {
+                    let pairs = decl
+                        .inputs
+                        .into_pairs()
+                        .filter(|pair| !matches!(pair.value(), FnArg::Inherit(_)));
+                    let pairs = pairs.map(|pair| match pair {
                         Pair::Punctuated(item, sep) => Pair::Punctuated(item.into_upstream(), sep),
                         Pair::End(item) => {
                             Pair::<syn::FnArg, syn::token::Comma>::End(item.into_upstream())
@@ -350,17 +1001,259 @@ impl ItemFn {
     }
 }
 
+/// Attributes are emitted as outer attributes, since the inner ones were
+/// merged into them.
+fn outer_attrs_to_tokens(attrs: &[syn::Attribute], tokens: &mut proc_macro2::TokenStream) {
+    for attr in attrs {
+        let mut attr = attr.clone();
+        attr.style = syn::AttrStyle::Outer;
+        attr.to_tokens(tokens);
+    }
+}
+
+impl ItemFn {
+    /// The `..inherit()` directives of the parameter list.
+    pub fn inherits_mut(&mut self) -> impl Iterator<Item = &mut Inherit> {
+        self.decl.inputs.iter_mut().filter_map(|arg| match arg {
+            FnArg::Inherit(ref mut inherit) => Some(inherit),
+            _ => None,
+        })
+    }
+}
+
+/// Emits the function in the syntax of `fancyargs!()`, so that it can be
+/// parsed again after passing through another macro.
+impl quote::ToTokens for ItemFn {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        outer_attrs_to_tokens(&self.attrs, tokens);
+        self.vis.to_tokens(tokens);
+        self.constness.to_tokens(tokens);
+        self.unsafety.to_tokens(tokens);
+        self.asyncness.to_tokens(tokens);
+        self.abi.to_tokens(tokens);
+        self.decl.fn_token.to_tokens(tokens);
+        self.ident.to_tokens(tokens);
+        self.decl.generics.to_tokens(tokens);
+        self.decl.paren_token.surround(tokens, |tokens| {
+            let args = self.decl.inputs.iter().filter_map(|arg| match arg {
+                FnArg::Captured(cap) => {
+                    let attrs = &cap.attrs;
+                    Some(quote!( #( #attrs )* #cap ))
+                }
+                FnArg::Inherit(Inherit {
+                    resolved: Some(ref args),
+                    ..
+                }) if args.is_empty() => None,
+                arg => Some(quote!( #arg )),
+            });
+            tokens.extend(quote!( #( #args ),* ));
+        });
+        self.decl.output.to_tokens(tokens);
+        self.decl.generics.where_clause.to_tokens(tokens);
+        self.block.to_tokens(tokens);
+    }
+}
+
+/// Code generation options, set with `#[fancy(...)]`.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// Generate a self-contained `macro_rules!` instead of forwarding to
+    /// `invoke!()`.
+    pub declarative: bool,
+    /// Path of the fancyargs crate at the call site, `::fancyargs` if not set.
+    pub crate_path: Option<proc_macro2::TokenStream>,
+}
+
+impl Options {
+    /// Parses the comma separated arguments of a `#[fancy(...)]` attribute.
+    pub fn parse_args(&mut self, input: parse::ParseStream) -> parse::Result<()> {
+        while !input.is_empty() {
+            if input.peek(Token![crate]) {
+                input.parse::<Token![crate]>()?;
+                input.parse::<Token![=]>()?;
+                let path: syn::LitStr = input.parse()?;
+                let tokens = path
+                    .value()
+                    .parse()
+                    .map_err(|_| parse::Error::new(path.span(), "invalid crate path"))?;
+                self.crate_path = Some(tokens);
+            } else {
+                let arg: syn::Ident = input.parse()?;
+                if arg == "declarative" {
+                    self.declarative = true;
+                } else {
+                    return Err(parse::Error::new(arg.span(), "unknown option"));
+                }
+            }
+            if input.is_empty() {
+                break;
+            }
+            input.parse::<Token![,]>()?;
+        }
+        Ok(())
+    }
+
+    /// Removes all `#[fancy(...)]` attributes from `attrs`, and returns the
+    /// options with the attribute arguments applied.
+    pub fn extract(&self, attrs: &mut Vec<syn::Attribute>) -> parse::Result<Options> {
+        let mut options = self.clone();
+        let mut result = Ok(());
+        attrs.retain(|attr| {
+            if !attr.path.is_ident("fancy") {
+                return true;
+            }
+            if result.is_ok() && !attr.tts.is_empty() {
+                result = (|input: parse::ParseStream| {
+                    let content;
+                    parenthesized!(content in input);
+                    options.parse_args(&content)
+                })
+                .parse2(attr.tts.clone());
+            }
+            false
+        });
+        result.map(|_| options)
+    }
+}
+
+#[derive(Debug)]
+pub struct ItemImpl {
+    pub attrs: Vec<syn::Attribute>,
+    pub unsafety: Option<syn::token::Unsafe>,
+    pub impl_token: syn::token::Impl,
+    pub generics: syn::Generics,
+    pub self_ty: Box<syn::Type>,
+    pub items: Vec<ItemFn>,
+}
+
+impl ItemImpl {
+    /// The path used to call associated functions of the implemented type.
+    ///
+    /// Generic arguments are stripped (`Foo<T>` becomes `Foo`), since the
+    /// type parameters are not in scope at the call site and must be inferred.
+    pub fn self_path(&self) -> Option<syn::Path> {
+        match *self.self_ty {
+            syn::Type::Path(ref ty) if ty.qself.is_none() => {
+                let mut path = ty.path.clone();
+                for segment in path.segments.iter_mut() {
+                    segment.arguments = syn::PathArguments::None;
+                }
+                Some(path)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl quote::ToTokens for ItemImpl {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        outer_attrs_to_tokens(&self.attrs, tokens);
+        self.unsafety.to_tokens(tokens);
+        self.impl_token.to_tokens(tokens);
+        self.generics.to_tokens(tokens);
+        self.self_ty.to_tokens(tokens);
+        self.generics.where_clause.to_tokens(tokens);
+        let items = &self.items;
+        tokens.extend(quote!( { #( #items )* } ));
+    }
+}
+
+impl parse::Parse for ItemImpl {
+    fn parse(input: parse::ParseStream) -> parse::Result<Self> {
+        let outer_attrs = input.call(syn::Attribute::parse_outer)?;
+        let unsafety: Option<Token![unsafe]> = input.parse()?;
+        let impl_token: Token![impl] = input.parse()?;
+
+        let has_generics = input.peek(Token![<])
+            && (input.peek2(Token![>])
+                || input.peek2(Token![#])
+                || (input.peek2(syn::Ident) || input.peek2(syn::Lifetime))
+                    && (input.peek3(Token![:])
+                        || input.peek3(Token![,])
+                        || input.peek3(Token![>])));
+        let generics: syn::Generics = if has_generics {
+            input.parse()?
+        } else {
+            syn::Generics::default()
+        };
+
+        let self_ty: syn::Type = input.parse()?;
+        if input.peek(Token![for]) {
+            return Err(input.error("fancyargs!() only supports inherent impl blocks"));
+        }
+        let where_clause: Option<syn::WhereClause> = input.parse()?;
+
+        let content;
+        syn::braced!(content in input);
+        let inner_attrs = content.call(syn::Attribute::parse_inner)?;
+
+        let mut items = Vec::new();
+        while !content.is_empty() {
+            items.push(content.parse()?);
+        }
+
+        Ok(ItemImpl {
+            attrs: attrs(outer_attrs, inner_attrs),
+            unsafety,
+            impl_token,
+            generics: syn::Generics {
+                where_clause,
+                ..generics
+            },
+            self_ty: Box::new(self_ty),
+            items,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum Item {
+    Fn(ItemFn),
+    Impl(ItemImpl),
+}
+
+impl Item {
+    /// The `..inherit()` directives of the parameter lists, in order.
+    pub fn inherits_mut(&mut self) -> Vec<&mut Inherit> {
+        match self {
+            Item::Fn(item) => item.inherits_mut().collect(),
+            Item::Impl(item) => item.items.iter_mut().flat_map(ItemFn::inherits_mut).collect(),
+        }
+    }
+}
+
+impl quote::ToTokens for Item {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        match self {
+            Item::Fn(item) => item.to_tokens(tokens),
+            Item::Impl(item) => item.to_tokens(tokens),
+        }
+    }
+}
+
+impl parse::Parse for Item {
+    fn parse(input: parse::ParseStream) -> parse::Result<Self> {
+        let ahead = input.fork();
+        ahead.call(syn::Attribute::parse_outer)?;
+        if ahead.peek(Token![impl]) || (ahead.peek(Token![unsafe]) && ahead.peek2(Token![impl])) {
+            input.parse().map(Item::Impl)
+        } else {
+            input.parse().map(Item::Fn)
+        }
+    }
+}
+
 pub struct MacroBody {
-    pub fns: Vec<ItemFn>,
+    pub items: Vec<Item>,
 }
 
 impl parse::Parse for MacroBody {
     fn parse(input: parse::ParseStream) -> parse::Result<Self> {
-        let mut fns = Vec::new();
+        let mut items = Vec::new();
         while !input.is_empty() {
-            fns.push(input.parse()?);
+            items.push(input.parse()?);
         }
-        Ok(Self { fns })
+        Ok(Self { items })
     }
 }
 
@@ -384,7 +1277,7 @@ impl parse::Parse for ItemFn {
 
         let content;
         let paren_token = parenthesized!(content in input);
-        let inputs = content.parse_terminated(FnArg::parse)?;
+        let inputs = content.call(fn_inputs)?;
         let variadic: Option<Token![...]> = match inputs.last() {
             Some(syn::punctuated::Pair::End(&FnArg::Captured(ArgCaptured {
                 ty: syn::Type::Verbatim(syn::TypeVerbatim { ref tts }),
@@ -403,26 +1296,26 @@ impl parse::Parse for ItemFn {
 
         Ok(ItemFn {
             attrs: attrs(outer_attrs, inner_attrs),
-            vis: vis,
-            constness: constness,
-            unsafety: unsafety,
-            asyncness: asyncness,
-            abi: abi,
-            ident: ident,
+            vis,
+            constness,
+            unsafety,
+            asyncness,
+            abi,
+            ident,
             decl: Box::new(FnDecl {
-                fn_token: fn_token,
-                paren_token: paren_token,
-                inputs: inputs,
-                output: output,
-                variadic: variadic,
+                fn_token,
+                paren_token,
+                inputs,
+                output,
+                variadic,
                 generics: syn::Generics {
-                    where_clause: where_clause,
+                    where_clause,
                     ..generics
                 },
             }),
             block: Box::new(syn::Block {
-                brace_token: brace_token,
-                stmts: stmts,
+                brace_token,
+                stmts,
             }),
         })
     }
@@ -432,6 +1325,88 @@ impl parse::Parse for ItemFn {
 pub struct InvokationArg {
     pub name: Option<syn::Ident>,
     pub expr: syn::Expr,
+    /// Whether the argument was written with the flag shorthand.
+    pub flag: bool,
+}
+
+impl InvokationArg {
+    /// Turns a bare `name` into `name = true` and `!name` into
+    /// `name = false` if `name` is the keyword of a flag argument.
+    ///
+    /// A variable with the name of a flag can be passed positionally by
+    /// wrapping it in parentheses (`(name)`), which are removed so they
+    /// don't trigger the `unused_parens` lint.
+    fn into_flag(mut self, arg_definitions: &Punctuated<FnArg, token::Comma>) -> Self {
+        if self.name.is_some() {
+            return self;
+        }
+        let is_flag = |ident: &syn::Ident| {
+            arg_definitions
+                .iter()
+                .filter_map(FnArg::captured)
+                .filter(|def| def.is_flag())
+                .any(|def| {
+                    def.name() == Some(ident.to_string())
+                        || def.aliases.iter().any(|alias| alias.name == *ident)
+                })
+        };
+        let (ident, value) = match self.expr {
+            syn::Expr::Paren(syn::ExprParen { ref attrs, ref expr, .. }) if attrs.is_empty() => {
+                if let syn::Expr::Path(ref path) = **expr {
+                    if flag_ident(path).filter(|ident| is_flag(ident)).is_some() {
+                        self.expr = syn::Expr::Path(path.clone());
+                    }
+                }
+                return self;
+            }
+            syn::Expr::Path(ref path) => (flag_ident(path), true),
+            syn::Expr::Unary(syn::ExprUnary {
+                ref attrs,
+                op: syn::UnOp::Not(_),
+                ref expr,
+            }) if attrs.is_empty() => match **expr {
+                syn::Expr::Path(ref path) => (flag_ident(path), false),
+                _ => (None, false),
+            },
+            _ => (None, false),
+        };
+        let ident = match ident {
+            Some(ref ident) if is_flag(ident) => ident.clone(),
+            _ => return self,
+        };
+        let span = self.expr.span();
+        InvokationArg {
+            name: Some(ident),
+            expr: syn::Expr::Lit(syn::ExprLit {
+                attrs: Vec::new(),
+                lit: syn::Lit::Bool(syn::LitBool { value, span }),
+            }),
+            flag: true,
+        }
+    }
+}
+
+/// The identifier of a path expression that consists of a single
+/// identifier.
+fn flag_ident(path: &syn::ExprPath) -> Option<syn::Ident> {
+    match path {
+        syn::ExprPath {
+            attrs,
+            qself: None,
+            path: syn::Path {
+                leading_colon: None,
+                segments,
+            },
+        } if attrs.is_empty() && segments.len() == 1 => {
+            let segment = segments.first().unwrap().into_value();
+            if segment.arguments.is_empty() {
+                Some(segment.ident.clone())
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
 }
 
 impl Parse for InvokationArg {
@@ -446,15 +1421,71 @@ impl Parse for InvokationArg {
 
         let expr = input.parse()?;
 
-        Ok(InvokationArg { name, expr })
+        Ok(InvokationArg {
+            name,
+            expr,
+            flag: false,
+        })
+    }
+}
+
+/// An explicit generic argument of a macro call (`T = u32` or `N = 4`).
+#[derive(Debug)]
+pub struct GenericArg {
+    pub name: syn::Ident,
+    /// The type, or the const argument, as it is written in a turbofish.
+    pub value: proc_macro2::TokenStream,
+}
+
+impl GenericArg {
+    /// Parses a generic argument if the keyword is a type or const parameter
+    /// of the function, otherwise it is a regular keyword argument.
+    fn parse(generics: &syn::Generics, input: parse::ParseStream) -> parse::Result<Option<Self>> {
+        if !(input.peek(syn::Ident) && input.peek2(token::Eq)) {
+            return Ok(None);
+        }
+        let name = input.fork().parse::<syn::Ident>()?;
+        let is_type = match generics.params.iter().find_map(|param| match param {
+            syn::GenericParam::Type(param) if param.ident == name => Some(true),
+            syn::GenericParam::Const(param) if param.ident == name => Some(false),
+            _ => None,
+        }) {
+            Some(is_type) => is_type,
+            None => return Ok(None),
+        };
+        let name = input.parse()?;
+        input.parse::<token::Eq>()?;
+        let value = if is_type {
+            let ty: syn::Type = input.parse()?;
+            quote!( #ty )
+        } else {
+            // Only literals and blocks can be written as const argument
+            // without braces.
+            match input.parse()? {
+                expr @ syn::Expr::Lit(_) | expr @ syn::Expr::Block(_) => quote!( #expr ),
+                expr => quote!( { #expr } ),
+            }
+        };
+        Ok(Some(GenericArg { name, value }))
     }
 }
 
 #[derive(Debug)]
 pub struct InvokationInput {
+    /// Set for const functions, which have no wrapper.
+    pub constness: Option<Token![const]>,
     pub target_fn_path: syn::Path,
+    /// Generic parameters of the impl block the function is defined in.
+    pub impl_generics: syn::Generics,
+    /// Generic parameters of the function.
+    pub generics: syn::Generics,
     pub arg_definitions: Punctuated<FnArg, token::Comma>,
     pub args: Punctuated<InvokationArg, token::Comma>,
+    /// Explicit generic arguments, which are not part of `args`.
+    pub generic_args: Vec<GenericArg>,
+    /// Whether the call starts with `@partial`, which returns a closure that
+    /// takes the missing required arguments.
+    pub partial: bool,
 }
 
 impl InvokationInput {
@@ -464,24 +1495,123 @@ impl InvokationInput {
             .filter_map(|item| item.captured().cloned())
             .collect()
     }
+
+    /// Whether the target is a method that takes `self` as first argument.
+    pub fn has_receiver(&self) -> bool {
+        self.arg_definitions
+            .first()
+            .map(|pair| pair.value().is_receiver())
+            .unwrap_or(false)
+    }
+
+    /// How the receiver is bound before the call: by reference for `&self`
+    /// and `&mut self`, by value for `self`.
+    pub fn receiver_borrow(&self) -> proc_macro2::TokenStream {
+        match self.arg_definitions.first().map(|pair| pair.into_value()) {
+            Some(FnArg::SelfRef(arg)) => {
+                let mutability = &arg.mutability;
+                quote!( & #mutability )
+            }
+            _ => quote!(),
+        }
+    }
+}
+
+/// The input of the hidden `invoke!()` macro: a call of the function, or an
+/// item with an `..inherit()` directive, after `@fancyargs_inherit`.
+///
+/// The macro of a function passes both on to `invoke!()`, so that rustdoc
+/// shows a single arm for it.
+pub enum InvokeInput {
+    Call(InvokationInput),
+    Inherit(InheritInput),
 }
 
-impl Parse for InvokationInput {
+impl Parse for InvokeInput {
     fn parse(input: parse::ParseStream) -> parse::Result<Self> {
-        let target_fn_path = input.parse()?;
+        let constness = input.parse()?;
+        let target_fn_path = input.call(syn::Path::parse_mod_style)?;
+        let impl_generics = input.parse()?;
+        let generics = input.parse()?;
 
-        let mut inner;
+        let inner;
         parenthesized!(inner in input);
-        let arg_definitions = Punctuated::parse_terminated(&inner)?;
+        let arg_definitions: Punctuated<FnArg, token::Comma> = Punctuated::parse_terminated(&inner)?;
         input.parse::<token::Semi>()?;
-        let args = Punctuated::parse_terminated(input)?;
+
+        let ahead = input.fork();
+        let inherit = ahead.parse::<Token![@]>().is_ok()
+            && ahead.parse::<syn::Ident>().map(|ident| ident == "fancyargs_inherit").unwrap_or(false);
+        if inherit {
+            input.parse::<Token![@]>()?;
+            input.parse::<syn::Ident>()?;
+            let receiver = arg_definitions.iter().filter(|arg| arg.is_receiver());
+            let args = arg_definitions.iter().filter_map(FnArg::captured).map(|arg| {
+                let attrs = &arg.attrs;
+                quote!( #( #attrs )* #arg )
+            });
+            let last = Inherited {
+                fn_path: quote!( #target_fn_path ),
+                receiver: quote!( #( #receiver )* ),
+                args: quote!( #( #args ),* ),
+            };
+            return InheritInput::parse_rest(last, input).map(InvokeInput::Inherit);
+        }
+        InvokationInput::parse_call(constness, target_fn_path, impl_generics, generics, arg_definitions, input)
+            .map(InvokeInput::Call)
+    }
+}
+
+impl InvokationInput {
+    /// Parses the arguments of a call, after the definition of the function.
+    fn parse_call(
+        constness: Option<Token![const]>,
+        target_fn_path: syn::Path,
+        impl_generics: syn::Generics,
+        generics: syn::Generics,
+        arg_definitions: Punctuated<FnArg, token::Comma>,
+        input: parse::ParseStream,
+    ) -> parse::Result<Self> {
+        let has_receiver = arg_definitions
+            .first()
+            .map(|pair| pair.value().is_receiver())
+            .unwrap_or(false);
+
+        let partial = if input.peek(Token![@]) {
+            input.parse::<Token![@]>()?;
+            let mode: syn::Ident = input.parse()?;
+            if mode != "partial" {
+                return Err(parse::Error::new(mode.span(), "Unknown macro mode, expected '@partial'"));
+            }
+            true
+        } else {
+            false
+        };
+
+        let mut args = Punctuated::new();
+        let mut generic_args = Vec::new();
+        while !input.is_empty() {
+            match GenericArg::parse(&generics, input)? {
+                Some(arg) => generic_args.push(arg),
+                // The receiver of a method is never a flag.
+                None if args.is_empty() && has_receiver => args.push(input.parse()?),
+                None => args.push(input.parse::<InvokationArg>()?.into_flag(&arg_definitions)),
+            }
+            if input.is_empty() {
+                break;
+            }
+            input.parse::<token::Comma>()?;
+        }
 
         Ok(Self {
+            constness,
             target_fn_path,
+            impl_generics,
+            generics,
             arg_definitions,
             args,
+            generic_args,
+            partial,
         })
     }
 }
-
-impl InvokationInput {}
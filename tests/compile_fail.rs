@@ -0,0 +1,8 @@
+//! Checks the errors of invalid calls, the expected compiler output is in
+//! the `.stderr` files next to the cases in `tests/ui`.
+
+#[test]
+fn test_compile_fail() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,448 @@
+extern crate fancyargs;
+
+use fancyargs::{fancy, fancyargs};
+
+fancyargs!(
+    #[fancy(declarative)]
+    fn kw1(a: &str, b: &str = "b", d: &str = "d") -> String {
+        format!("{}{}{}", a, b, d)
+    }
+
+    #[fancy(declarative)]
+    fn full1(a: &str, b: &str = "b_default", c?: Option<&str>, d*: Vec<&str>) -> String {
+        format!("{}{}{}{}", a, b, c.unwrap_or(""), d.join(""))
+    }
+
+    #[fancy(declarative)]
+    fn kw_only1(a: u8, *, b: u8 = 2, c: u8, rest*: Vec<u8>) -> Vec<u8> {
+        let mut values = vec![a, b, c];
+        values.extend(rest);
+        values
+    }
+
+    #[fancy(declarative)]
+    fn pos_only1(a: u8, b: u8 = 2, /, c: u8 = 3) -> u8 {
+        a * 100 + b * 10 + c
+    }
+
+    #[fancy(declarative)]
+    fn range1(start: usize, end: usize = start + 10, step: usize = (end - start) / 5, extra*: Vec<usize>) -> Vec<usize> {
+        (start..end).step_by(step).chain(extra).collect()
+    }
+
+    #[fancy(declarative)]
+    fn slice1(items*: &[u8]) -> u8 {
+        items.iter().sum()
+    }
+
+    #[fancy(declarative)]
+    fn pattern1((a, b): (u8, u8), _: &str, point @ (x, y): (u8, u8) = (0, 1)) -> u8 {
+        a + b + x * 10 + y
+    }
+);
+
+#[fancy(declarative)]
+fn attr1(a: u8, #[default(2)] b: u8, #[vararg] c: Vec<u8>) -> u8 {
+    a + b + c.iter().sum::<u8>()
+}
+
+#[fancy(declarative)]
+fn generic1<T: std::str::FromStr + Default + Copy, const N: usize>(
+    input: &str,
+    #[optional] fallback: Option<T>,
+) -> [T; N] {
+    [input.parse().ok().or(fallback).unwrap_or_default(); N]
+}
+
+#[fancy(declarative)]
+fn cfg1(
+    /// Only exists outside of tests.
+    #[cfg(not(test))]
+    missing: NotATest,
+    a: u8,
+    #[cfg(test)]
+    #[default(a * 2)]
+    b: u8,
+) -> u8 {
+    a + b
+}
+
+#[fancy(declarative)]
+fn alias1(a: u8, #[alias(old_b, bb)] #[default(1)] b: u8) -> u8 {
+    a * 10 + b
+}
+
+#[fancy(declarative)]
+fn alias2(#[alias(r#type)] #[default(1)] kind: u8) -> u8 {
+    kind
+}
+
+#[fancy(declarative)]
+fn flag1(name: &str, #[default(false)] verbose: bool, #[default(true)] cache: bool) -> String {
+    format!("{}:{}:{}", name, verbose, cache)
+}
+
+#[fancy(declarative)]
+fn order1(a: u8, #[default(0)] b: u8, #[default(a)] c: u8, #[vararg] rest: Vec<u8>) -> (u8, u8, u8, Vec<u8>) {
+    (a, b, c, rest)
+}
+
+#[fancy(declarative)]
+fn reborrow1(buf: &mut [u8], #[default(1)] value: u8) {
+    for byte in buf.iter_mut() {
+        *byte += value;
+    }
+}
+
+#[fancy(declarative)]
+fn partial2(level: String, message: &str) -> String {
+    format!("{}: {}", level, message)
+}
+
+#[fancy(declarative)]
+fn partial3(path: &std::path::Path, mode: u8) -> String {
+    format!("{}:{}", path.display(), mode)
+}
+
+#[fancy(declarative)]
+fn partial1(host: &str, port: u16, #[default(30)] timeout: u32, #[default(timeout / 10)] retries: u32) -> String {
+    format!("{}:{} {} {}", host, port, timeout, retries)
+}
+
+#[fancy(declarative)]
+fn into1(#[into] name: String, #[into] #[vararg] parts: Vec<String>) -> String {
+    format!("{}:{}", name, parts.join(","))
+}
+
+#[fancy(declarative)]
+fn opt_type1(#[optional] a: std::option::Option<u8>, #[optional_type] suffix: String) -> String {
+    format!("{}{}", a.unwrap_or(0), suffix)
+}
+
+pub struct Counter {
+    count: u32,
+}
+
+#[fancy(declarative)]
+impl Counter {
+    fn new(#[default(0)] count: u32) -> Self {
+        Counter { count }
+    }
+
+    fn add(&mut self, #[default(1)] amount: u32) -> u32 {
+        self.count += amount;
+        self.count
+    }
+
+    fn add_times(&mut self, #[default(1)] amount: u32, #[default(1)] times: u32) -> u32 {
+        self.count += amount * times;
+        self.count
+    }
+}
+
+fancyargs!(
+    #[fancy(declarative)]
+    fn inherit1(..inherit(partial1), *, scheme: &str = "tcp") -> String {
+        format!("{}://{}", scheme, forward!(partial1))
+    }
+);
+
+#[test]
+fn test_kw1() {
+    assert_eq!(kw1!("a"), "abd");
+    assert_eq!(kw1!(a = "a"), "abd");
+    assert_eq!(kw1!("a", "b1"), "ab1d");
+    assert_eq!(kw1!("a", d = "d1", b = "b1"), "ab1d1");
+    assert_eq!(kw1!(d = "d1", b = "b1", a = "a1",), "a1b1d1");
+}
+
+#[test]
+fn test_nested() {
+    // Every call defines the helper macro of the muncher, nested calls
+    // define it again.
+    assert_eq!(kw1!(&kw1!("a"), d = &kw1!("x", b = &kw1!("y"))), "abdbxybdd");
+    assert_eq!(attr1!(attr1!(1), b = attr1!(2, 3), attr1!(1, 1, 1)), 11);
+}
+
+#[test]
+fn test_full1() {
+    assert_eq!(full1!("a", "b1", "c", "d1", "d2"), "ab1cd1d2");
+    assert_eq!(full1!(a = "a", b = "b1", c = "c", "d1", "d2"), "ab1cd1d2");
+    assert_eq!(full1!("a"), "ab_default");
+    assert_eq!(full1!("a", c = "c", "d1", "d2"), "ab_defaultcd1d2");
+    assert_eq!(full1!("a", d = vec!["d1"]), "ab_defaultd1");
+}
+
+#[test]
+fn test_kw_only1() {
+    assert_eq!(kw_only1!(1, c = 3), vec![1, 2, 3]);
+    assert_eq!(kw_only1!(1, 4, 5, c = 3, b = 0), vec![1, 0, 3, 4, 5]);
+    assert_eq!(kw_only1!(c = 3, a = 1, 4), vec![1, 2, 3, 4]);
+}
+
+#[test]
+fn test_pos_only1() {
+    assert_eq!(pos_only1!(1), 123);
+    assert_eq!(pos_only1!(1, 0, 0), 100);
+    assert_eq!(pos_only1!(1, c = 0), 120);
+}
+
+#[test]
+fn test_range1() {
+    let end = 100;
+    assert_eq!(range1!(0), vec![0, 2, 4, 6, 8]);
+    assert_eq!(range1!(5, step = 3), vec![5, 8, 11, 14]);
+    assert_eq!(range1!(0, end), (0..100).step_by(20).collect::<Vec<_>>());
+    assert_eq!(range1!(step = 5, start = 10), vec![10, 15]);
+    assert_eq!(range1!(0, 4, 2, 1, 2), vec![0, 2, 1, 2]);
+    assert_eq!(range1!(0, 4, 2, ..vec![1, 2], 3), vec![0, 2, 1, 2, 3]);
+    assert_eq!(range1!(0, 4, 2, 1, ..2..4), vec![0, 2, 1, 2, 3]);
+}
+
+#[test]
+fn test_slice1() {
+    assert_eq!(slice1!(), 0);
+    assert_eq!(slice1!(1, 2, 3), 6);
+    assert_eq!(slice1!(items = &[1, 2]), 3);
+}
+
+#[test]
+fn test_pattern1() {
+    assert_eq!(pattern1!((1, 2), "ignored"), 4);
+    assert_eq!(pattern1!((1, 2), "ignored", (3, 4)), 37);
+    assert_eq!(pattern1!((1, 2), "ignored", point = (3, 0)), 33);
+}
+
+#[test]
+fn test_attr1() {
+    assert_eq!(attr1!(1), 3);
+    assert_eq!(attr1!(1, 1, 1, 1), 4);
+    assert_eq!(attr1!(b = 0, a = 1), 1);
+}
+
+#[test]
+fn test_generic1() {
+    assert_eq!(generic1!("1", T = u8, N = 2), [1, 1]);
+    assert_eq!(generic1!(N = 1 + 2, T = i8, "x"), [0, 0, 0]);
+    assert_eq!(generic1!("x", fallback = 4u16, N = 1), [4]);
+    let arr: [u32; 2] = generic1!("5");
+    assert_eq!(arr, [5, 5]);
+}
+
+#[test]
+fn test_cfg1() {
+    assert_eq!(cfg1!(1), 3);
+    assert_eq!(cfg1!(b = 0, a = 1), 1);
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_alias1() {
+    assert_eq!(alias1!(1), 11);
+    assert_eq!(alias1!(1, old_b = 3), 13);
+    assert_eq!(alias1!(bb = 4, a = 1), 14);
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_alias2() {
+    assert_eq!(alias2!(), 1);
+    assert_eq!(alias2!(r#type = 2), 2);
+}
+
+#[test]
+fn test_flag1() {
+    let verbose = false;
+    assert_eq!(flag1!(name = "a", verbose), "a:true:true");
+    assert_eq!(flag1!(name = "a", !cache), "a:false:false");
+    assert_eq!(flag1!(name = "b", !cache, verbose), "b:true:false");
+    assert_eq!(flag1!("a", (verbose), cache = true), "a:false:true");
+    assert_eq!(flag1!("a", verbose = verbose, !cache), "a:false:false");
+}
+
+/// Records the order in which the arguments are evaluated.
+fn logged<T>(log: &std::cell::RefCell<Vec<&'static str>>, name: &'static str, value: T) -> T {
+    log.borrow_mut().push(name);
+    value
+}
+
+#[test]
+fn test_order1() {
+    let log = std::cell::RefCell::new(Vec::new());
+    assert_eq!(order1!(b = logged(&log, "b", 2), a = logged(&log, "a", 1)), (1, 2, 1, vec![]));
+    assert_eq!(log.replace(Vec::new()), ["b", "a"]);
+    assert_eq!(
+        order1!(logged(&log, "a", 1), c = logged(&log, "c", 3), b = 2, logged(&log, "r", 4)),
+        (1, 2, 3, vec![4])
+    );
+    assert_eq!(log.replace(Vec::new()), ["a", "c", "r"]);
+    assert_eq!(order1!(rest = logged(&log, "r", vec![4]), a = logged(&log, "a", 1)), (1, 0, 1, vec![4]));
+    assert_eq!(log.replace(Vec::new()), ["r", "a"]);
+}
+
+#[test]
+fn test_reborrow1() {
+    // A `&mut` variable is reborrowed like in a plain call, not moved.
+    let mut bytes = [0u8; 2];
+    let buf = &mut bytes[..];
+    reborrow1!(buf);
+    reborrow1!(buf, value = 2);
+    // Also if the arguments are reordered and bound to temporaries.
+    reborrow1!(value = std::convert::identity(2), buf = buf);
+    reborrow1!(buf);
+    assert_eq!(bytes, [6, 6]);
+}
+
+#[test]
+fn test_partial1() {
+    let log = std::cell::RefCell::new(Vec::new());
+    let connect = partial1!(@partial host = "db", timeout = logged(&log, "timeout", 5));
+    assert_eq!(log.replace(Vec::new()), ["timeout"]);
+    assert_eq!(connect(80), "db:80 5 0");
+    assert_eq!(connect(81), "db:81 5 0");
+    assert!(log.borrow().is_empty());
+    let connect = partial1!(@partial port = 80);
+    assert_eq!(connect("db"), "db:80 30 3");
+    assert_eq!(partial1!(@partial "db", 80, retries = 1)(), "db:80 30 1");
+}
+
+#[test]
+fn test_partial2() {
+    // `String` is not `Copy`, so the closure can only be called once.
+    let warn = partial2!(@partial level = String::from("warn"));
+    assert_eq!(warn("disk full"), "warn: disk full");
+}
+
+#[test]
+fn test_partial3() {
+    // A reference parameter takes a new borrow on every call.
+    let open = partial3!(@partial mode = 1);
+    for name in ["a", "bb"] {
+        let path = std::path::PathBuf::from(name);
+        assert_eq!(open(&path), format!("{}:1", name));
+    }
+}
+
+#[test]
+fn test_inherit1() {
+    assert_eq!(inherit1!("db", 80), "tcp://db:80 30 3");
+    assert_eq!(inherit1!("db", 80, timeout = 50, scheme = "udp"), "udp://db:80 50 5");
+}
+
+#[test]
+fn test_into1() {
+    assert_eq!(into1!("a"), "a:");
+    assert_eq!(into1!("a", "b", ..["c"], String::from("d")), "a:b,c,d");
+    assert_eq!(into1!(name = "n"), "n:");
+}
+
+#[test]
+fn test_opt_type1() {
+    assert_eq!(opt_type1!(), "0");
+    assert_eq!(opt_type1!(1, "s"), "1s");
+    assert_eq!(opt_type1!(suffix = "s"), "0s");
+}
+
+mod exported {
+    use fancyargs::fancy;
+
+    #[fancy(declarative)]
+    pub fn exported1(a: u8, #[default(1)] b: u8) -> u8 {
+        a + b
+    }
+}
+
+#[fancy(declarative)]
+fn raw1<T: Copy>(r#type: T, #[default(1)] r#in: u8) -> (T, u8) {
+    (r#type, r#in)
+}
+
+mod typed {
+    use fancyargs::fancy;
+
+    mod helpers {
+        pub fn greeting(name: &str) -> String {
+            format!("Hello, {}!", name)
+        }
+    }
+
+    pub struct Cfg(pub u8);
+
+    #[fancy(declarative)]
+    pub fn greet1(name: &str, #[default(helpers::greeting(name))] greeting: String) -> String {
+        format!("{} ({})", greeting, name.len())
+    }
+
+    #[fancy(declarative)]
+    pub fn typed1(c: Cfg, #[default(1)] n: u8, #[vararg] rest: Vec<Cfg>) -> u8 {
+        c.0 + n + rest.iter().map(|c| c.0).sum::<u8>()
+    }
+}
+
+#[test]
+fn test_raw1() {
+    assert_eq!(raw1!('a'), ('a', 1));
+    assert_eq!(raw1!(r#in = 2, r#type = 3), (3, 2));
+}
+
+#[test]
+fn test_typed1() {
+    use typed::typed1;
+    assert_eq!(typed1!(typed::Cfg(1), n = 2), 3);
+    assert_eq!(typed1!(typed::Cfg(1), 0, typed::Cfg(2), ..vec![typed::Cfg(3)]), 6);
+}
+
+#[test]
+fn test_greet1() {
+    use typed::greet1;
+    assert_eq!(greet1!("John"), "Hello, John! (4)");
+    assert_eq!(greet1!(greeting = "Hi".to_string(), name = "John"), "Hi (4)");
+}
+
+#[test]
+fn test_exported1() {
+    use exported::exported1;
+    assert_eq!(exported1!(1), 2);
+    assert_eq!(exported1!(b = 2, a = 1), 3);
+}
+
+#[test]
+fn test_impl() {
+    let mut counter = counter_new!();
+    assert_eq!(counter_add!(counter), 1);
+    assert_eq!(counter_add!(counter, amount = 5), 6);
+    assert_eq!(counter_new!(count = 3).count, 3);
+}
+
+/// Adds through a reference, which is not a `mut` variable.
+fn add_twice(counter: &mut Counter) -> u32 {
+    counter_add_times!(counter, times = 2, amount = 1)
+}
+
+#[test]
+fn test_impl_order() {
+    // The receiver is evaluated first, and borrowed rather than moved.
+    let log = std::cell::RefCell::new(Vec::new());
+    let mut counter = counter_new!();
+    assert_eq!(
+        counter_add_times!(
+            logged(&log, "recv", &mut counter),
+            times = logged(&log, "times", 2),
+            amount = logged(&log, "amount", 3)
+        ),
+        6
+    );
+    assert_eq!(log.replace(Vec::new()), ["recv", "times", "amount"]);
+    assert_eq!(counter_add_times!(counter, times = logged(&log, "times", 2)), 8);
+    assert_eq!(add_twice(&mut counter), 10);
+}
+
+#[test]
+fn test_impl_partial() {
+    // The owned receiver is borrowed mutably, so the closure is `FnMut`.
+    let mut counter = counter_new!();
+    let mut add = counter_add_times!(@partial counter, times = 2);
+    assert_eq!(add(), 2);
+    assert_eq!(add(), 4);
+    assert_eq!(counter.count, 4);
+}
@@ -0,0 +1,54 @@
+//! Exports fancy functions, so that tests can call their macros from another
+//! crate, and check their docs.
+
+use fancyargs::{fancy, fancyargs};
+
+/// Lets crates that only depend on this one call the macros.
+#[doc(hidden)]
+pub use fancyargs as __fancyargs;
+
+fancyargs!(
+    /// Greets someone.
+    #[fancy(crate = "$crate::__fancyargs")]
+    pub fn greet(
+        /// Who to greet.
+        name: &str,
+        punct: &str = "!",
+    ) -> String {
+        format!("Hello {}{}", name, punct)
+    }
+);
+
+/// Adds numbers.
+#[fancy(declarative, crate = "$crate::__fancyargs")]
+pub fn sum(
+    a: u8,
+    #[default(1)] b: u8,
+    /// More numbers, `a | b`.
+    #[vararg]
+    rest: Vec<u8>,
+) -> u8 {
+    a + b + rest.iter().sum::<u8>()
+}
+
+fancyargs!(
+    /// Describes a plot.
+    #[fancy(crate = "$crate::__fancyargs")]
+    pub fn plot(width: u32, height: u32 = width / 2, title: String = format!("{}x{}", width, height)) -> String {
+        format!("{}: {}x{}", title, width, height)
+    }
+
+    /// Describes a plot with a caption.
+    #[fancy(crate = "$crate::__fancyargs")]
+    pub fn captioned(caption: &str, ..inherit(plot)) -> String {
+        format!("{} ({})", forward!(plot), caption)
+    }
+);
+
+fancyargs!(
+    /// Adds numbers and scales the sum.
+    #[fancy(crate = "$crate::__fancyargs")]
+    pub fn scaled_sum(scale: u8, ..inherit(sum)) -> u8 {
+        forward!(sum) * scale
+    }
+);
@@ -0,0 +1,100 @@
+//! Checks the docs of the macros of the test dependency, as rendered by
+//! rustdoc.
+
+use std::{env, fs, path::Path, process::Command};
+
+/// Documents the test dependency and returns the page of a macro.
+fn macro_page(name: &str) -> String {
+    let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("docs");
+    let output = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+        .args(["doc", "--no-deps", "--package", "fancyargs-test-dep", "--target-dir"])
+        .arg(&target_dir)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("failed to run cargo doc");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let page = target_dir.join("doc/fancyargs_test_dep").join(format!("macro.{}.html", name));
+    fs::read_to_string(page).expect("missing macro page")
+}
+
+/// Text of the HTML, without tags.
+fn text(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for ch in html.chars() {
+        match ch {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            ch if !in_tag => text.push(ch),
+            _ => {}
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Cells of the rows of the argument table.
+fn table_rows(page: &str) -> Vec<Vec<String>> {
+    let body = page.split("<tbody>").nth(1).expect("missing argument table");
+    let body = body.split("</tbody>").next().unwrap();
+    body.split("<tr>")
+        .skip(1)
+        .map(|row| row.split("<td").skip(1).map(|cell| text(&format!("<td{}", cell)).trim().to_string()).collect())
+        .collect()
+}
+
+/// The definition of the macro, as shown at the top of the page.
+fn definition(page: &str, name: &str) -> String {
+    let start = page.find(&format!("macro_rules! {}", name)).expect("missing definition");
+    let end = start + page[start..].find("</code>").unwrap();
+    text(&page[start..end])
+}
+
+#[test]
+fn test_docs() {
+    let greet = macro_page("greet");
+    assert_eq!(
+        definition(&greet, "greet"),
+        "macro_rules! greet {\n    ($($input:tt)*) => { ... };\n}"
+    );
+    assert!(text(&greet).contains("Greets someone."));
+    assert_eq!(
+        table_rows(&greet),
+        vec![
+            vec!["name", "&str", "required", "", "Who to greet."],
+            vec!["punct", "&str", "default", "\"!\"", ""],
+        ]
+    );
+
+    let sum = macro_page("sum");
+    // The internal arm of declarative macros is not documented.
+    assert_eq!(
+        definition(&sum, "sum"),
+        "macro_rules! sum {\n    ($($input:tt)*) => { ... };\n}"
+    );
+    assert!(text(&sum).contains("Adds numbers."));
+    assert_eq!(
+        table_rows(&sum),
+        vec![
+            vec!["a", "u8", "required", "", ""],
+            vec!["b", "u8", "default", "1", ""],
+            vec!["rest", "Vec<u8>", "vararg", "", "More numbers, a | b."],
+        ]
+    );
+
+    // Inherited defaults show the expressions of the source.
+    let captioned = macro_page("captioned");
+    assert!(text(&captioned).contains("Describes a plot with a caption."));
+    assert_eq!(
+        table_rows(&captioned),
+        vec![
+            vec!["caption", "&str", "required", "", ""],
+            vec!["width", "u32", "required", "", ""],
+            vec!["height", "u32", "default", "width / 2", ""],
+            vec!["title", "String", "default", "format!(\"{}x{}\", width, height)", ""],
+        ]
+    );
+}
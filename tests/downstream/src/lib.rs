@@ -0,0 +1,14 @@
+//! Calls the macros of a library without depending on fancyargs, building it
+//! checks that the macros reach fancyargs through the library.
+
+use fancyargs_test_dep::{captioned, greet, plot, sum};
+
+pub fn calls() -> [String; 5] {
+    [
+        greet!("a"),
+        greet!(punct = "?", name = "b"),
+        sum!(1, 2, 3).to_string(),
+        plot!(4),
+        captioned!("c", width = 6, height = 2),
+    ]
+}
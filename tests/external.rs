@@ -0,0 +1,50 @@
+//! Calls the macros of fancy functions defined in another crate.
+
+use fancyargs::fancyargs;
+use fancyargs_test_dep::{captioned, greet, plot, scaled_sum, sum};
+
+#[test]
+fn test_external() {
+    assert_eq!(greet!("a"), "Hello a!");
+    assert_eq!(greet!(punct = "?", name = "b"), "Hello b?");
+    assert_eq!(sum!(1), 2);
+    assert_eq!(sum!(1, 2, 3, 4), 10);
+    assert_eq!(scaled_sum!(2, 1, 2, 3), 12);
+}
+
+#[test]
+fn test_external_path() {
+    // The function must be in scope, then the macro can be called by path
+    // too.
+    assert_eq!(fancyargs_test_dep::greet!("c", "."), "Hello c.");
+    assert_eq!(fancyargs_test_dep::sum!(b = 0, a = 1), 1);
+}
+
+fancyargs!(
+    fn framed(..inherit(plot), *, border: u32 = 1) -> String {
+        format!("[{}] {}", border, forward!(plot))
+    }
+);
+
+#[test]
+fn test_external_inherit() {
+    // Only the function is imported, the defaults are computed by the
+    // wrapper of the inheriting function.
+    assert_eq!(plot!(4), "4x2: 4x2");
+    assert_eq!(captioned!("c", 6, height = 2), "6x2: 6x2 (c)");
+    assert_eq!(framed!(8), "[1] 8x4: 8x4");
+    assert_eq!(framed!(8, title = "t".to_string(), border = 0), "[0] t: 8x4");
+}
+
+#[test]
+fn test_downstream() {
+    assert_eq!(
+        fancyargs_test_downstream::calls(),
+        ["Hello a!", "Hello b?", "6", "4x2: 4x2", "6x2: 6x2 (c)"]
+    );
+}
+
+#[test]
+fn test_no_std() {
+    assert_eq!(fancyargs_test_no_std::calls(), [2, 10, 6, 7, 3, 4, 3, 9]);
+}
@@ -0,0 +1,53 @@
+//! Uses fancy functions in a `no_std` crate, building it checks that the
+//! generated code only depends on `core`.
+
+#![no_std]
+
+use fancyargs::{fancy, fancyargs};
+
+fancyargs!(
+    pub fn sum(first: u32, step: u32 = 1, rest*: &[u32]) -> u32 {
+        first + step + rest.iter().sum::<u32>()
+    }
+
+    pub fn max(floor?: Option<u8>, values*: impl IntoIterator<Item = u8>) -> u8 {
+        values.into_iter().fold(floor.unwrap_or(0), |max, value| if value > max { value } else { max })
+    }
+
+    #[fancy(declarative)]
+    pub fn first(fallback: u8, items*: [u8; 3]) -> u8 {
+        items.first().copied().unwrap_or(fallback)
+    }
+);
+
+pub struct Counter {
+    count: u32,
+}
+
+#[fancy]
+impl Counter {
+    pub fn new(#[default(0)] count: u32) -> Self {
+        Counter { count }
+    }
+
+    pub fn add(&mut self, #[default(1)] amount: u32, #[vararg] more: &[u32]) -> u32 {
+        self.count += amount + more.iter().sum::<u32>();
+        self.count
+    }
+}
+
+pub fn calls() -> [u32; 8] {
+    let mut counter = counter_new!();
+    let upper = max!(0, ..[1, 2].iter().copied(), 3);
+    let add_two = sum!(@partial step = 2);
+    [
+        sum!(1),
+        sum!(1, 2, 3, 4),
+        sum!(step = 0, first = 5, rest = &[1]),
+        max!(floor = 2, values = [1, 7, 3]) as u32,
+        upper as u32,
+        first!(0, 4, 5, 6) as u32,
+        add_two(1),
+        counter_add!(counter, 2, 3, 4),
+    ]
+}
@@ -1,8 +1,7 @@
-#![feature(proc_macro_hygiene)]
-
 extern crate fancyargs;
 
-use fancyargs::fancyargs;
+use fancyargs::{fancy, fancyargs};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
 
 fancyargs!(
     fn kw1(a: &str, b: &str = "b", d: &str = "d") -> String {
@@ -13,6 +12,71 @@ fancyargs!(
         items
     }
 
+    fn varargs2(queue*: VecDeque<u8>) -> VecDeque<u8> {
+        queue
+    }
+
+    fn varargs3(unique*: BTreeSet<&str>) -> Vec<&str> {
+        unique.into_iter().collect()
+    }
+
+    fn varargs4(boxed*: Box<[u8]>) -> Box<[u8]> {
+        boxed
+    }
+
+    fn varargs5(sep: char = ' ', text*: String) -> String {
+        text.replace(' ', &sep.to_string())
+    }
+
+    fn slice1(items*: &[u8]) -> u8 {
+        items.iter().sum()
+    }
+
+    fn array1(first: u8, items*: [u8; 3]) -> u8 {
+        first + items.iter().sum::<u8>()
+    }
+
+    fn iter1(items*: impl IntoIterator<Item = u8>) -> u8 {
+        items.into_iter().sum()
+    }
+
+    fn iter2(items*: impl Iterator<Item = u8>) -> u8 {
+        items.sum()
+    }
+
+    fn default1(a: u8 = default) -> u8 {
+        a
+    }
+
+    fn generic1<'a, T: std::fmt::Display>(
+        prefix: &'a str,
+        value: T,
+        suffix: &'a str = "!",
+        items*: Vec<T>,
+    ) -> (String, &'a str) {
+        (format!("{}{}{}", prefix, value, items.len()), suffix)
+    }
+
+    fn generic2<T: std::str::FromStr + Default>(input: &str, fallback?: Option<T>) -> T {
+        input.parse().ok().or(fallback).unwrap_or_default()
+    }
+
+    fn generic3<T: Copy, const N: usize>(value: T) -> [T; N] {
+        [value; N]
+    }
+
+    fn cfg1(
+        /// The first value.
+        a: u8,
+        #[cfg(not(test))] missing: NotATest,
+        /// Only exists in tests.
+        #[cfg(test)]
+        b: u8 = a + 1,
+        #[cfg(not(test))] c: u8 = 10,
+    ) -> u8 {
+        a + b
+    }
+
     fn opt1(a?: Option<bool>) -> bool {
         a.unwrap_or(false)
     }
@@ -20,8 +84,93 @@ fancyargs!(
     fn full1(a: &str, b: &str = "b_default", c?: Option<&str>, d*: Vec<&str>) -> String {
         format!("{}{}{}{}", a, b, c.unwrap_or(""), d.join(""))
     }
+
+    fn kw_only1(a: u8, *, b: u8 = 2, c: u8, rest*: Vec<u8>) -> Vec<u8> {
+        let mut values = vec![a, b, c];
+        values.extend(rest);
+        values
+    }
+
+    fn pos_only1(a: u8, b: u8 = 2, /, c: u8 = 3) -> u8 {
+        a * 100 + b * 10 + c
+    }
+
+    fn kwargs1(tag: &str, children*: Vec<&str>, attrs**: BTreeMap<&'static str, &str>) -> String {
+        let attrs = attrs
+            .iter()
+            .map(|(key, value)| format!(" {}=\"{}\"", key, value))
+            .collect::<String>();
+        format!("<{}{}>{}</{}>", tag, attrs, children.join(""), tag)
+    }
+
+    fn kwargs2(a: u8, /, extra**: HashMap<&'static str, u8>) -> u8 {
+        a + extra.values().sum::<u8>()
+    }
+
+    fn range1(start: usize, end: usize = start + 10, step: usize = (end - start) / 5) -> Vec<usize> {
+        (start..end).step_by(step).collect()
+    }
+
+    fn pattern1((a, b): (u8, u8), _: &str, point @ (x, y): (u8, u8) = (0, 1)) -> u8 {
+        a + b + x * 10 + y
+    }
+
+    fn opt2(a?: std::option::Option<u8>, b?: ::core::option::Option<u8>) -> u8 {
+        a.unwrap_or(1) + b.unwrap_or(2)
+    }
+
+    fn opt3(#[optional_type] a?: MaybeU8, #[optional_type] suffix: String) -> String {
+        format!("{}{}", a.unwrap_or(0), suffix)
+    }
+
+    fn alias1(a: u8, #[alias(old_b, deprecated = "renamed to b")] #[alias(bb)] b: u8 = 1) -> u8 {
+        a * 10 + b
+    }
+
+    fn alias2(#[alias(r#type)] kind: u8 = 1) -> u8 {
+        kind
+    }
+
+    fn raw1(r#type: String, r#in: Vec<u8> = vec![1]) -> String {
+        format!("{}{:?}", r#type, r#in)
+    }
+
+    fn flag1(name: &str, verbose: bool = false, #[alias(caching)] cache: bool = true) -> String {
+        format!("{}:{}:{}", name, verbose, cache)
+    }
+
+    fn order1(a: u8, b: u8 = 0, c: u8 = a, rest*: Vec<u8>) -> (u8, u8, u8, Vec<u8>) {
+        (a, b, c, rest)
+    }
+
+    fn reborrow1(n: &mut i32, by: i32 = 1) -> i32 {
+        *n += by;
+        *n
+    }
+
+    fn partial1(host: &str, port: u16, timeout: u32 = 30, retries: u32 = timeout / 10) -> String {
+        format!("{}:{} {} {}", host, port, timeout, retries)
+    }
+
+    fn into1(name~: String, suffix~?: Option<String>, parts*~: Vec<String>) -> String {
+        format!("{}{}:{}", name, suffix.unwrap_or_default(), parts.join(","))
+    }
+
+    fn partial2(level: String, message: &str) -> String {
+        format!("{}: {}", level, message)
+    }
+
+    fn partial3(path: &std::path::Path, mode: u8) -> String {
+        format!("{}:{}", path.display(), mode)
+    }
+
+    fn inherit1(name: &str, ..inherit(partial1)) -> String {
+        format!("{}: {}", name, forward!(partial1))
+    }
 );
 
+type MaybeU8 = Option<u8>;
+
 #[test]
 fn test_full1() {
     assert_eq!(full1!("a", "b1", "c", "d1", "d2"), "ab1cd1d2".to_string());
@@ -56,12 +205,615 @@ fn test_varargs1() {
 }
 
 #[test]
+fn test_varargs_collections() {
+    assert_eq!(varargs2!(1, 2), VecDeque::from(vec![1, 2]));
+    assert_eq!(varargs2!(), VecDeque::new());
+    assert_eq!(varargs3!("b", "a", "b"), vec!["a", "b"]);
+    assert_eq!(varargs4!(1, 2, 3).len(), 3);
+    assert_eq!(varargs4!(boxed = Box::new([1, 2])).len(), 2);
+    assert_eq!(varargs5!('-', "a b", "c"), "a-bc");
+}
+
+#[test]
+fn test_varargs_no_alloc() {
+    assert_eq!(slice1!(), 0);
+    assert_eq!(slice1!(1, 2, 3), 6);
+    assert_eq!(slice1!(items = &[1, 2]), 3);
+    assert_eq!(array1!(1, 2, 3, 4), 10);
+    assert_eq!(iter1!(1, 2), 3);
+    assert_eq!(iter1!(), 0);
+    assert_eq!(iter1!(items = vec![1, 2, 3]), 6);
+    assert_eq!(iter2!(4, 5), 9);
+}
+
+#[test]
+fn test_varargs_spread() {
+    let existing = vec![true, false];
+    assert_eq!(varargs1!(..existing.clone(), true), vec![true, false, true]);
+    assert_eq!(varargs1!(false, ..existing, ..vec![true]), vec![false, true, false, true]);
+    assert_eq!(varargs1!(..Vec::new()), vec![]);
+    let names = ["a", "b"];
+    assert_eq!(varargs5!('-', "x ", ..names.iter().cloned()), "x-ab");
+    assert_eq!(iter1!(1, ..2..4, 5), 11);
+    assert_eq!(iter2!(..vec![1, 2]), 3);
+}
+
+#[test]
+fn test_generic1() {
+    assert_eq!(generic1!("", 1), ("10".to_string(), "!"));
+    assert_eq!(
+        generic1!("-", "a", suffix = &String::from("?"), "b", "c").0,
+        "-a2"
+    );
+}
+
+#[test]
+fn test_generic2() {
+    assert_eq!(generic2!(T = u32, "12"), 12);
+    assert_eq!(generic2!("x", T = i8), 0);
+    assert_eq!(generic2!("x", fallback = 3u8), 3);
+    assert_eq!(generic2!(T = String, "a"), "a");
+    assert_eq!(generic3!(1u8, N = 2), [1, 1]);
+    assert_eq!(generic3!(N = 1 + 1, T = u16, value = 3), [3, 3]);
+    assert_eq!(generic3!(N = { 2 * 2 }, 'a').len(), 4);
+}
+
+#[test]
+fn test_cfg1() {
+    assert_eq!(cfg1!(1), 3);
+    assert_eq!(cfg1!(1, 1), 2);
+    assert_eq!(cfg1!(b = 0, a = 1), 1);
+}
+
+#[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_opt1() {
     assert_eq!(opt1!(), false);
     assert_eq!(opt1!(false), false);
     assert_eq!(opt1!(true), true);
 }
 
+#[test]
+fn test_pattern1() {
+    assert_eq!(pattern1!((1, 2), "ignored"), 4);
+    assert_eq!(pattern1!((1, 2), "ignored", (3, 4)), 37);
+    assert_eq!(pattern1!((1, 2), "ignored", point = (3, 0)), 33);
+}
+
+#[test]
+fn test_kw_only1() {
+    assert_eq!(kw_only1!(1, c = 3), vec![1, 2, 3]);
+    assert_eq!(kw_only1!(1, 4, 5, c = 3, b = 0), vec![1, 0, 3, 4, 5]);
+    assert_eq!(kw_only1!(c = 3, a = 1, 4), vec![1, 2, 3, 4]);
+}
+
+#[test]
+fn test_kwargs1() {
+    assert_eq!(kwargs1!("br"), "<br></br>");
+    assert_eq!(
+        kwargs1!("input", r#type = "text", id = "name"),
+        r#"<input id="name" type="text"></input>"#
+    );
+    assert_eq!(
+        kwargs1!(tag = "p", class = "big", "a", "b"),
+        r#"<p class="big">ab</p>"#
+    );
+    assert_eq!(kwargs2!(1), 1);
+    assert_eq!(kwargs2!(1, a = 2, b = 3), 6);
+}
+
+#[test]
+fn test_range1() {
+    let end = 100;
+    assert_eq!(range1!(0), vec![0, 2, 4, 6, 8]);
+    assert_eq!(range1!(5, step = 3), vec![5, 8, 11, 14]);
+    assert_eq!(range1!(0, end), (0..100).step_by(20).collect::<Vec<_>>());
+    assert_eq!(range1!(step = 5, start = 10), vec![10, 15]);
+}
+
+#[test]
+fn test_opt2() {
+    assert_eq!(opt2!(), 3);
+    assert_eq!(opt2!(b = 5), 6);
+}
+
+#[test]
+fn test_opt3() {
+    assert_eq!(opt3!(), "0");
+    assert_eq!(opt3!(7), "7");
+    assert_eq!(opt3!(suffix = "s"), "0s");
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_alias1() {
+    assert_eq!(alias1!(1), 11);
+    assert_eq!(alias1!(1, b = 2), 12);
+    assert_eq!(alias1!(1, old_b = 3), 13);
+    assert_eq!(alias1!(bb = 4, a = 1), 14);
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_alias2() {
+    assert_eq!(alias2!(), 1);
+    assert_eq!(alias2!(r#type = 2), 2);
+}
+
+#[test]
+fn test_raw1() {
+    assert_eq!(raw1!(String::from("a")), "a[1]");
+    assert_eq!(raw1!(r#in = vec![2], r#type = String::from("b")), "b[2]");
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_flag1() {
+    let verbose = false;
+    assert_eq!(flag1!(name = "a", verbose), "a:true:true");
+    assert_eq!(flag1!(name = "a", !cache), "a:false:false");
+    assert_eq!(flag1!(name = "b", !cache, verbose), "b:true:false");
+    assert_eq!(flag1!("a", (verbose), caching = true), "a:false:true");
+    assert_eq!(flag1!("a", verbose = verbose, !caching), "a:false:false");
+    assert_eq!(flag1!("a", cache = false, !verbose), "a:false:false");
+}
+
+/// Records the order in which the arguments are evaluated.
+fn logged<T>(log: &std::cell::RefCell<Vec<&'static str>>, name: &'static str, value: T) -> T {
+    log.borrow_mut().push(name);
+    value
+}
+
+#[test]
+fn test_order1() {
+    let log = std::cell::RefCell::new(Vec::new());
+    assert_eq!(order1!(b = logged(&log, "b", 2), a = logged(&log, "a", 1)), (1, 2, 1, vec![]));
+    assert_eq!(log.replace(Vec::new()), ["b", "a"]);
+    assert_eq!(
+        order1!(logged(&log, "a", 1), c = logged(&log, "c", 3), b = 2, logged(&log, "r", 4)),
+        (1, 2, 3, vec![4])
+    );
+    assert_eq!(log.replace(Vec::new()), ["a", "c", "r"]);
+    assert_eq!(order1!(logged(&log, "a", 1), logged(&log, "b", 2)), (1, 2, 1, vec![]));
+    assert_eq!(log.replace(Vec::new()), ["a", "b"]);
+    // Variables are read when they are passed, not when the arguments are
+    // reordered.
+    let mut counter = 1;
+    assert_eq!(order1!(b = counter, a = { counter += 1; counter }), (2, 1, 2, vec![]));
+}
+
+#[test]
+fn test_reborrow1() {
+    // A `&mut` variable is reborrowed like in a plain call, not moved.
+    let mut value = 0;
+    let n = &mut value;
+    assert_eq!(reborrow1!(n), 1);
+    assert_eq!(reborrow1!(n, by = 2), 3);
+    // Also if the arguments are reordered and bound to temporaries.
+    assert_eq!(reborrow1!(by = std::convert::identity(2), n = n), 5);
+    assert_eq!(reborrow1!(n), 6);
+}
+
+#[test]
+fn test_partial1() {
+    let log = std::cell::RefCell::new(Vec::new());
+    let connect = partial1!(@partial host = "db", timeout = logged(&log, "timeout", 5));
+    assert_eq!(log.replace(Vec::new()), ["timeout"]);
+    assert_eq!(connect(80), "db:80 5 0");
+    assert_eq!(connect(81), "db:81 5 0");
+    assert!(log.borrow().is_empty());
+    let connect = partial1!(@partial port = 80);
+    assert_eq!(connect("db"), "db:80 30 3");
+    assert_eq!(partial1!(@partial "db", 80, retries = 1)(), "db:80 30 1");
+}
+
+#[test]
+fn test_partial2() {
+    // `String` is not `Copy`, so the closure can only be called once.
+    let warn = partial2!(@partial level = String::from("warn"));
+    assert_eq!(warn("disk full"), "warn: disk full");
+}
+
+#[test]
+fn test_partial3() {
+    // A reference parameter takes a new borrow on every call.
+    let open = partial3!(@partial mode = 1);
+    for name in ["a", "bb"] {
+        let path = std::path::PathBuf::from(name);
+        assert_eq!(open(&path), format!("{}:1", name));
+    }
+}
+
+#[test]
+fn test_into1() {
+    assert_eq!(into1!("a"), "a:");
+    assert_eq!(into1!(String::from("a"), "b", "c", String::from("d")), "ab:c,d");
+    assert_eq!(into1!(suffix = "s", name = "n", ..vec!["x", "y"]), "ns:x,y");
+    assert_eq!(into1!(name = "n", parts = vec![String::from("p")]), "n:p");
+}
+
+#[test]
+fn test_pos_only1() {
+    assert_eq!(pos_only1!(1), 123);
+    assert_eq!(pos_only1!(1, 0, 0), 100);
+    assert_eq!(pos_only1!(1, c = 0), 120);
+}
+
+#[fancy]
+fn attr_pos_only1(a: u8, #[pos_only] b: u8, #[default(3)] c: u8) -> u8 {
+    a * 100 + b * 10 + c
+}
+
+#[test]
+fn test_attr_pos_only1() {
+    assert_eq!(attr_pos_only1!(1, 2), 123);
+    assert_eq!(attr_pos_only1!(1, 2, c = 0), 120);
+}
+
+#[fancy]
+fn attr_kw_only1(a: u8, #[kw_only] #[default(2)] b: u8, c: u8) -> u8 {
+    a * 100 + b * 10 + c
+}
+
+#[test]
+fn test_attr_kw_only1() {
+    assert_eq!(attr_kw_only1!(1, c = 3), 123);
+    assert_eq!(attr_kw_only1!(c = 3, b = 0, a = 1), 103);
+}
+
+#[fancy]
+fn attr1(
+    a: &str,
+    #[default("b_default")] b: &str,
+    #[optional] c: Option<&str>,
+    #[default] e: u8,
+    #[vararg] d: Vec<&str>,
+) -> String {
+    format!("{}{}{}{}{}", a, b, c.unwrap_or(""), e, d.join(""))
+}
+
+#[test]
+fn test_attr1() {
+    assert_eq!(attr1!("a"), "ab_default0".to_string());
+    assert_eq!(attr1!("a", c = "c", e = 1), "ab_defaultc1".to_string());
+    assert_eq!(
+        attr1!("a", "b1", "c", 2, "d1", "d2"),
+        "ab1c2d1d2".to_string()
+    );
+    assert_eq!(default1!(), 0);
+    assert_eq!(default1!(a = 3), 3);
+}
+
+pub struct Client {
+    host: String,
+}
+
+fancyargs!(
+    impl Client {
+        fn new(host: &str = "localhost") -> Self {
+            Client { host: host.to_string() }
+        }
+
+        fn connect(&self, port: u16 = 80, secure?: Option<bool>) -> String {
+            let scheme = if secure.unwrap_or(false) { "https" } else { "http" };
+            format!("{}://{}:{}", scheme, self.host, port)
+        }
+    }
+);
+
+pub struct ConnectionPool {
+    size: u8,
+}
+
+fancyargs!(
+    impl ConnectionPool {
+        fn new(size: u8 = 4) -> Self {
+            ConnectionPool { size }
+        }
+    }
+);
+
+pub struct Tally {
+    total: u32,
+}
+
+fancyargs!(
+    impl Tally {
+        fn add(&mut self, amount: u32, times: u32 = 1) -> u32 {
+            self.total += amount * times;
+            self.total
+        }
+    }
+);
+
+#[test]
+fn test_impl() {
+    let client = client_new!();
+    assert_eq!(client.host, "localhost");
+    let client = client_new!(host = "example.com");
+    assert_eq!(client_connect!(client), "http://example.com:80");
+    assert_eq!(client_connect!(client, 8080), "http://example.com:8080");
+    assert_eq!(
+        client_connect!(&client, secure = true, port = 443),
+        "https://example.com:443"
+    );
+    assert_eq!(connection_pool_new!().size, 4);
+    assert_eq!(connection_pool_new!(size = 8).size, 8);
+}
+
+#[test]
+fn test_impl_order() {
+    // The receiver is evaluated first, and borrowed rather than moved.
+    let log = std::cell::RefCell::new(Vec::new());
+    let mk = || logged(&log, "recv", client_new!());
+    assert_eq!(
+        client_connect!(mk(), secure = logged(&log, "secure", true), port = logged(&log, "port", 443)),
+        "https://localhost:443"
+    );
+    assert_eq!(log.replace(Vec::new()), ["recv", "secure", "port"]);
+    let client = client_new!();
+    assert_eq!(client_connect!(client, secure = logged(&log, "secure", false), port = 1), "http://localhost:1");
+    assert_eq!(client.host, "localhost");
+}
+
+#[test]
+fn test_impl_partial() {
+    // The owned receiver is borrowed mutably, so the closure is `FnMut`.
+    let mut tally = Tally { total: 0 };
+    let mut add = tally_add!(@partial tally, times = 2);
+    assert_eq!(add(1), 2);
+    assert_eq!(add(2), 6);
+    assert_eq!(tally.total, 6);
+    let client = client_new!();
+    let connect = client_connect!(@partial client, secure = true);
+    assert_eq!(connect(), "https://localhost:80");
+    assert_eq!(connect(), "https://localhost:80");
+    assert_eq!(client.host, "localhost");
+}
+
+mod http {
+    pub struct Http;
+
+    fancyargs::fancyargs!(
+        impl Http {
+            pub fn connect(&self, port: u16 = 80) -> String {
+                format!("http:{}", port)
+            }
+        }
+    );
+}
+
+mod ftp {
+    pub struct Ftp;
+
+    fancyargs::fancyargs!(
+        impl Ftp {
+            pub fn connect(&self, port: u16 = 21) -> String {
+                format!("ftp:{}", port)
+            }
+        }
+    );
+}
+
+#[test]
+fn test_impl_same_method() {
+    // Exported macros of methods with the same name don't collide.
+    assert_eq!(http_connect!(http::Http), "http:80");
+    assert_eq!(ftp_connect!(ftp::Ftp, port = 22), "ftp:22");
+}
+
+fancyargs!(
+    impl Client {
+        fn connect_all(&self, ..inherit(client_connect), *, times: usize = 2) -> String {
+            vec![forward!(client_connect, self); times].join(" ")
+        }
+    }
+);
+
+#[test]
+fn test_inherit1() {
+    assert_eq!(inherit1!("a", "db", 80), "a: db:80 30 3");
+    assert_eq!(inherit1!(port = 80, name = "a", host = "db", timeout = 50), "a: db:80 50 5");
+    let client = client_new!();
+    assert_eq!(client_connect_all!(client, 81, times = 1), "http://localhost:81");
+    assert_eq!(
+        client_connect_all!(client, secure = true),
+        "https://localhost:80 https://localhost:80"
+    );
+}
+
+mod scoped {
+    use fancyargs::fancyargs;
+
+    const TIMEOUT: u32 = 30;
+
+    fn default_name() -> String {
+        "scoped".to_string()
+    }
+
+    mod helpers {
+        pub fn greeting(name: &str) -> String {
+            format!("Hello, {}!", name)
+        }
+
+        pub fn len() -> usize {
+            2
+        }
+    }
+
+    pub struct Server {
+        pub timeout: u32,
+    }
+
+    pub struct Stack<T> {
+        pub items: Vec<T>,
+        pub capacity: usize,
+    }
+
+    fancyargs!(
+        pub fn scoped1(name: String = default_name(), timeout: u32 = TIMEOUT, suffix: &str = "!") -> String {
+            format!("{}{}{}", name, timeout, suffix)
+        }
+
+        impl Server {
+            pub fn listen(timeout: u32 = TIMEOUT * 2) -> Self {
+                Server { timeout }
+            }
+        }
+
+        pub fn scoped2(start: u32, end: u32 = start + TIMEOUT, chars: std::str::Chars = "abc".chars()) -> String {
+            format!("{}{}{}", start, end, chars.as_str())
+        }
+
+        pub fn scoped4(name: &str, greeting: String = helpers::greeting(name)) -> String {
+            format!("{} ({})", greeting, name.len())
+        }
+
+        impl<T: Clone> Stack<T> {
+            pub fn new(capacity: usize = TIMEOUT as usize) -> Self {
+                Stack { items: Vec::new(), capacity }
+            }
+
+            pub fn filled(first: T, rest: Vec<T> = vec![first.clone(); helpers::len()]) -> Self {
+                let mut items = vec![first];
+                items.extend(rest);
+                Stack { capacity: items.len(), items }
+            }
+        }
+    );
+}
+
+#[test]
+fn test_scoped1() {
+    use scoped::{scoped1, Server};
+    assert_eq!(scoped1!(), "scoped30!");
+    assert_eq!(scoped1!(timeout = 1), "scoped1!");
+    assert_eq!(server_listen!().timeout, 60);
+}
+
+#[test]
+fn test_scoped2() {
+    use scoped::scoped2;
+    assert_eq!(scoped2!(1), "131abc");
+    assert_eq!(scoped2!(1, end = 2, chars = "d".chars()), "12d");
+}
+
+#[test]
+fn test_scoped4() {
+    use scoped::{scoped4, Stack};
+    assert_eq!(scoped4!("John"), "Hello, John! (4)");
+    assert_eq!(scoped4!("John", greeting = "Hi".to_string()), "Hi (4)");
+    let stack: Stack<u8> = stack_new!();
+    assert_eq!(stack.capacity, 30);
+    assert_eq!(stack_filled!('a').items, vec!['a', 'a', 'a']);
+    assert_eq!(stack_filled!('a', rest = vec!['b']).items, vec!['a', 'b']);
+}
+
+#[test]
+fn test_scoped3() {
+    const OFFSET: u8 = 3;
+
+    fancyargs!(
+        fn scoped3(a: u8, b: u8 = a + OFFSET) -> u8 {
+            a + b
+        }
+    );
+
+    assert_eq!(scoped3!(1), 5);
+    assert_eq!(scoped3!(1, 1), 2);
+}
+
+#[test]
+fn test_same_name() {
+    // Literal defaults are not hoisted, so there is no hidden struct that
+    // takes the name of the function.
+    #[allow(dead_code)]
+    mod config {}
+    use std::fmt;
+
+    fancyargs!(
+        fn config(a: u8, items: usize = 0) -> usize {
+            a as usize + items
+        }
+
+        fn fmt(align: fmt::Alignment, fill: &str = "-") -> String {
+            format!("{:?}{}", align, fill)
+        }
+    );
+
+    assert_eq!(config!(1), 1);
+    assert_eq!(config!(1, items = 2), 3);
+    assert_eq!(fmt!(fmt::Alignment::Left), "Left-");
+}
+
+mod typed {
+    use fancyargs::fancyargs;
+    use std::collections::HashMap;
+
+    #[derive(Debug, PartialEq)]
+    pub struct Cfg(pub u8);
+
+    pub struct Builder;
+
+    fancyargs!(
+        pub fn typed1(c: Cfg, n: u8 = 1, o?: Option<Cfg>, chars: std::str::Chars = "".chars(), rest*: Vec<Cfg>) -> u8 {
+            c.0 + n + o.map_or(0, |o| o.0) + chars.count() as u8 + rest.iter().map(|c| c.0).sum::<u8>()
+        }
+
+        pub fn typed2(extra**: HashMap<&'static str, Cfg>) -> u8 {
+            extra.values().map(|c| c.0).sum()
+        }
+
+        impl Builder {
+            pub fn build(c: Cfg, n: u8 = 1) -> u8 {
+                c.0 + n
+            }
+        }
+    );
+}
+
+#[test]
+fn test_typed1() {
+    use typed::{typed1, typed2, Builder};
+    assert_eq!(typed1!(typed::Cfg(1), n = 2), 3);
+    assert_eq!(typed1!(typed::Cfg(1), 0, typed::Cfg(2), "ab".chars(), typed::Cfg(3), typed::Cfg(4)), 12);
+    assert_eq!(typed1!(typed::Cfg(1), rest = vec![typed::Cfg(5)]), 7);
+    assert_eq!(typed1!(typed::Cfg(1), 0, typed::Cfg(2), "".chars(), ..vec![typed::Cfg(5)]), 8);
+    assert_eq!(typed2!(a = typed::Cfg(1), b = typed::Cfg(2)), 3);
+    assert_eq!(builder_build!(typed::Cfg(1), n = 2), 3);
+}
+
+#[no_implicit_prelude]
+mod no_prelude {
+    use ::fancyargs::fancyargs;
+    use ::std::option::Option;
+
+    fancyargs!(
+        pub fn no_prelude1(a: u8, b: u8 = ::std::default::Default::default(), c?: Option<u8>, d*: ::std::vec::Vec<u8>) -> u8 {
+            a + b + c.unwrap_or(0) + d.len() as u8
+        }
+    );
+
+    #[test]
+    fn test_no_prelude1() {
+        ::std::assert_eq!(no_prelude1!(1), 1);
+        ::std::assert_eq!(no_prelude1!(1, 2, 3, 4, 5), 8);
+    }
+}
+
+/// Lets the `crate` option refer to fancyargs through this crate.
+use ::fancyargs as reexported_fancyargs;
+
+#[fancy(crate = "$crate::reexported_fancyargs")]
+fn crate_path1(#[default(1)] a: u8) -> u8 {
+    a
+}
+
+#[test]
+fn test_crate_path1() {
+    assert_eq!(crate_path1!(), 1);
+}
+
 mod child {
     #[test]
     fn test_nested() {
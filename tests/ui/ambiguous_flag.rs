@@ -0,0 +1,14 @@
+use fancyargs::fancyargs;
+
+fancyargs!(
+    fn render(tpl: &str, verbose: bool = false, rest*: Vec<u8>) -> String {
+        format!("{}:{}:{:?}", tpl, verbose, rest)
+    }
+);
+
+fn main() {
+    let verbose = true;
+    render!("index", verbose);
+    render!("index", !verbose);
+    render!(tpl = "index", 1, verbose);
+}
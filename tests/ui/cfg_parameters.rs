@@ -0,0 +1,13 @@
+use fancyargs::fancy;
+
+#[fancy]
+fn configure(
+    #[cfg(unix)] _a: u8,
+    #[cfg(windows)] _b: u8,
+    #[cfg(test)] _c: u8,
+    #[cfg(debug_assertions)] _d: u8,
+    #[cfg(not(unix))] _e: u8,
+) {
+}
+
+fn main() {}
@@ -0,0 +1,19 @@
+#![deny(deprecated)]
+
+use fancyargs::{fancy, fancyargs};
+
+fancyargs!(
+    fn connect(host: &str, #[alias(p)] port: u16 = 80) -> String {
+        format!("{}:{}", host, port)
+    }
+);
+
+#[fancy(declarative)]
+fn listen(#[alias(p)] #[default(80)] port: u16) -> u16 {
+    port
+}
+
+fn main() {
+    connect!("localhost", p = 8080);
+    listen!(p = 8080);
+}
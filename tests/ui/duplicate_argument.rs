@@ -0,0 +1,12 @@
+use fancyargs::fancyargs;
+
+fancyargs!(
+    fn connect(host: &str, port: u16 = 80) -> String {
+        format!("{}:{}", host, port)
+    }
+);
+
+fn main() {
+    connect!("localhost", port = 8080, port = 8081);
+    connect!("localhost", host = "example.com");
+}
@@ -0,0 +1,11 @@
+use fancyargs::fancyargs;
+
+fancyargs!(
+    fn connect(host: &str, port: u16 = 80) -> String {
+        format!("{}:{}", host, port)
+    }
+);
+
+fn main() {
+    connect!(host = "localhost", 8080);
+}
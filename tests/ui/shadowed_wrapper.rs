@@ -0,0 +1,17 @@
+#![allow(unused_macros)]
+
+use fancyargs::fancyargs;
+
+mod config {
+    pub fn load() -> u8 {
+        1
+    }
+}
+
+fancyargs!(
+    fn config(a: u8, b: u8 = config::load()) -> u8 {
+        a + b
+    }
+);
+
+fn main() {}
@@ -0,0 +1,12 @@
+use fancyargs::fancyargs;
+
+fancyargs!(
+    fn f(a: u8, b: u8 = 0) -> u8 {
+        a + b
+    }
+);
+
+fn main() {
+    f!(a = "5");
+    f!(1, b = "5");
+}
@@ -0,0 +1,11 @@
+use fancyargs::fancyargs;
+
+fancyargs!(
+    fn connect(host: &str, port: u16 = 80, timeout: u32 = 30) -> String {
+        format!("{}:{} {}", host, port, timeout)
+    }
+);
+
+fn main() {
+    connect!("localhost", timout = 5);
+}
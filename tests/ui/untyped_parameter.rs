@@ -0,0 +1,9 @@
+use fancyargs::fancyargs;
+
+fancyargs!(
+    fn connect(host, port: u16 = 80) -> String {
+        format!("{}:{}", host, port)
+    }
+);
+
+fn main() {}